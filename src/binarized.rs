@@ -7,7 +7,7 @@ use std::slice;
 use bit_vec::BitVec;
 
 use grammar::{ContextFree, ContextFreeRef, ContextFreeMut};
-use history::{Binarize, EliminateNulling, NullHistory};
+use history::{Binarize, EliminateNulling, NullHistory, Traced};
 use history::BinarizedRhsSubset::*;
 use rhs_closure::RhsClosure;
 use rule::{GrammarRule, RuleRef};
@@ -26,6 +26,8 @@ pub struct BinarizedCfg<H = NullHistory, Ss = ConsecutiveSymbols> where Ss: Symb
     /// The set of history values carried with the grammar's nulling rules, or empty if the grammar
     /// has no nulling rules.
     nulling: Vec<Option<H>>,
+    /// The array of start symbols.
+    start_symbols: Vec<Ss::Symbol>,
 }
 
 /// Compact representation of a binarized rule.
@@ -52,10 +54,12 @@ impl<H> BinarizedCfg<H> {
 impl<H, Ss> BinarizedCfg<H, Ss> where Ss: SymbolSource {
     /// Creates an empty BinarizedCfg with the given symbol source.
     pub fn with_sym_source(sym_source: Ss) -> BinarizedCfg<H, Ss> {
+        let start = sym_source.start_sym();
         BinarizedCfg {
             sym_source: sym_source,
             rules: vec![],
             nulling: vec![],
+            start_symbols: vec![start],
         }
     }
 
@@ -75,6 +79,7 @@ impl<H, Ss> BinarizedCfg<H, Ss> where Ss: SymbolSource {
         // Create a new grammar.
         let mut grammar = BinarizedCfg::with_sym_source(this.sym_source().clone());
         grammar.rules = Vec::with_capacity(new_rule_count);
+        grammar.start_symbols = this.start_symbols();
         // Insert all rules from one grammar into the other.
         for rule in this.rules() {
             grammar.rule(rule.lhs()).rhs_with_history(rule.rhs(), rule.history().clone());
@@ -94,6 +99,14 @@ impl<H, Ss> BinarizedCfg<H, Ss> where Ss: SymbolSource {
     }
 }
 
+impl<H, Ss> BinarizedCfg<Traced<H>, Ss> where H: Binarize, Ss: SymbolSource {
+    /// Returns the identifier of the original rule of the rule at `rule_idx` in the sequence of
+    /// rules, together with the position of the fragment within the binarized rule.
+    pub fn original_rule(&self, rule_idx: usize) -> Option<(usize, usize)> {
+        self.rules().nth(rule_idx).map(|rule| (rule.history().rule, rule.history().fragment))
+    }
+}
+
 impl<H, Ss> BinarizedCfg<H, Ss> where
             H: Binarize + Clone + EliminateNulling,
             Ss: SymbolSource + Clone {
@@ -105,6 +118,7 @@ impl<H, Ss> BinarizedCfg<H, Ss> where
     /// the empty string. Unproductive rules aren't preserved.
     pub fn eliminate_nulling_rules(&mut self) -> BinarizedCfg<H, Ss> {
         let mut nulling_grammar = BinarizedCfg::with_sym_source(self.sym_source().clone());
+        nulling_grammar.start_symbols = self.start_symbols.clone();
 
         if self.nulling.iter().any(|h| h.is_some()) {
             let mut nulling = mem::replace(&mut self.nulling, vec![]);
@@ -166,6 +180,10 @@ impl<H, Ss> ContextFree for BinarizedCfg<H, Ss> where
         &self.sym_source
     }
 
+    fn start_symbols(&self) -> Vec<Ss::Symbol> {
+        self.start_symbols.clone()
+    }
+
     fn binarize<'a>(&'a self) -> Self where
                 &'a Self: ContextFreeRef<'a>,
                 H: Clone,
@@ -283,7 +301,23 @@ impl<H, Ss> SymbolSource for BinarizedCfg<H, Ss> where Ss: SymbolSource {
     }
 
     fn start_sym(&self) -> Ss::Symbol {
-        self.sym_source.start_sym()
+        self.start_symbols[0]
+    }
+
+    fn has_names(&self) -> bool {
+        self.sym_source.has_names()
+    }
+
+    fn set_name(&mut self, sym: Self::Symbol, name: &str) {
+        self.sym_source.set_name(sym, name)
+    }
+
+    fn name_of(&self, sym: Self::Symbol) -> Option<&str> {
+        self.sym_source.name_of(sym)
+    }
+
+    fn sym_named(&mut self, name: &str) -> Self::Symbol {
+        self.sym_source.sym_named(name)
     }
 }
 
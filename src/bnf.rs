@@ -0,0 +1,222 @@
+//! Grammars can be read from BNF.
+//!
+//! A grammar is a list of rules and directives. Every rule has a symbol name on its LHS,
+//! followed by `::=` and alternatives separated by `|`, and ends with `;`. An alternative is a
+//! possibly empty list of symbol names, or `ε`. Names consist of alphanumeric characters and
+//! underscores. Symbols that aren't on the LHS of any rule are terminals.
+//!
+//! The `%start name` directive sets the start symbol. Without the directive, the LHS of the first
+//! rule is the start symbol. Comments are written as `// ...` and `/* ... */`.
+
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use grammar::Cfg;
+use history::Action;
+use rule_container::RuleContainer;
+use symbol::{NumericSymbol, SymbolSource};
+
+/// An error in a BNF grammar.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BnfError {
+    /// The line where the error was found, starting at 1.
+    pub line: usize,
+    /// The column where the error was found, starting at 1.
+    pub column: usize,
+    /// The kind of the error.
+    pub kind: BnfErrorKind,
+}
+
+/// The kinds of errors in BNF grammars.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BnfErrorKind {
+    /// A directive other than `%start`. Holds the directive's name.
+    UnknownDirective(String),
+    /// A directive without the name of a symbol.
+    MissingArgument,
+    /// An unexpected token. Holds the token, or nothing at the end of input.
+    Unexpected(Option<String>),
+    /// A comment that is never closed.
+    UnterminatedComment,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Name(String),
+    Directive(String),
+    Derives,
+    Alternative,
+    End,
+    Epsilon,
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+/// Reads a grammar from BNF. Returns the grammar and the symbols of all names.
+pub fn parse_bnf<H, Hs>(input: &str)
+        -> Result<(Cfg<H, Hs>, BTreeMap<String, NumericSymbol>), BnfError> where
+            H: Action + Default {
+    let mut cfg = Cfg::new();
+    let mut syms = BTreeMap::new();
+    let mut start = None;
+    let mut lexer = Lexer { input: input, chars: input.char_indices().peekable() };
+
+    while let Some((token, pos)) = try!(lexer.next_token()) {
+        match token {
+            Token::Directive(name) => {
+                if name != "start" {
+                    return Err(lexer.error(pos, BnfErrorKind::UnknownDirective(name)));
+                }
+                match try!(lexer.next_token()) {
+                    Some((Token::Name(name), _)) => {
+                        start = Some(sym(&mut cfg, &mut syms, name));
+                    }
+                    Some((_, pos)) => return Err(lexer.error(pos, BnfErrorKind::MissingArgument)),
+                    None => return Err(lexer.error(input.len(), BnfErrorKind::MissingArgument)),
+                }
+            }
+            Token::Name(name) => {
+                let lhs = sym(&mut cfg, &mut syms, name);
+                if start.is_none() {
+                    start = Some(lhs);
+                }
+                try!(lexer.expect(Token::Derives));
+                let mut rhs = vec![];
+                loop {
+                    match try!(lexer.next_token()) {
+                        Some((Token::Name(name), _)) => rhs.push(sym(&mut cfg, &mut syms, name)),
+                        Some((Token::Epsilon, _)) => {}
+                        Some((Token::Alternative, _)) => {
+                            cfg.add_rule(lhs, &rhs[..], H::default());
+                            rhs.clear();
+                        }
+                        Some((Token::End, _)) => {
+                            cfg.add_rule(lhs, &rhs[..], H::default());
+                            break;
+                        }
+                        other => return Err(lexer.unexpected(other)),
+                    }
+                }
+            }
+            other => return Err(lexer.unexpected(Some((other, pos)))),
+        }
+    }
+
+    if let Some(start) = start {
+        cfg.set_start(start);
+    }
+    Ok((cfg, syms))
+}
+
+/// Returns the symbol of a name, which is allocated if needed.
+fn sym<H, Hs>(cfg: &mut Cfg<H, Hs>, syms: &mut BTreeMap<String, NumericSymbol>, name: String)
+        -> NumericSymbol {
+    *syms.entry(name).or_insert_with(|| cfg.next_sym(false))
+}
+
+impl<'a> Lexer<'a> {
+    /// Returns the next token and its position, or `None` at the end of input.
+    fn next_token(&mut self) -> Result<Option<(Token, usize)>, BnfError> {
+        try!(self.skip_whitespace_and_comments());
+        let (pos, c) = match self.chars.next() {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        let token = match c {
+            '|' => Token::Alternative,
+            ';' => Token::End,
+            'ε' => Token::Epsilon,
+            ':' if self.input[pos..].starts_with("::=") => {
+                self.chars.next();
+                self.chars.next();
+                Token::Derives
+            }
+            '%' => Token::Directive(self.name(pos + 1)),
+            c if is_name_char(c) => Token::Name(self.name(pos)),
+            c => {
+                let kind = BnfErrorKind::Unexpected(Some(c.to_string()));
+                return Err(self.error(pos, kind));
+            }
+        };
+        Ok(Some((token, pos)))
+    }
+
+    /// Consumes the rest of a name that begins at `start`.
+    fn name(&mut self, start: usize) -> String {
+        while self.chars.peek().map_or(false, |&(_, c)| is_name_char(c)) {
+            self.chars.next();
+        }
+        let end = self.chars.peek().map_or(self.input.len(), |&(pos, _)| pos);
+        self.input[start .. end].to_string()
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), BnfError> {
+        while let Some(&(pos, c)) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.chars.next();
+            } else if self.input[pos..].starts_with("//") {
+                while let Some((_, c)) = self.chars.next() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else if self.input[pos..].starts_with("/*") {
+                match self.input[pos + 2 ..].find("*/") {
+                    Some(len) => {
+                        let end = pos + 2 + len + 2;
+                        while self.chars.peek().map_or(false, |&(pos, _)| pos < end) {
+                            self.chars.next();
+                        }
+                    }
+                    None => return Err(self.error(pos, BnfErrorKind::UnterminatedComment)),
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), BnfError> {
+        match try!(self.next_token()) {
+            Some((ref token, _)) if *token == expected => Ok(()),
+            other => Err(self.unexpected(other)),
+        }
+    }
+
+    fn unexpected(&self, token: Option<(Token, usize)>) -> BnfError {
+        match token {
+            Some((token, pos)) => {
+                let text = match token {
+                    Token::Name(name) => name,
+                    Token::Directive(name) => format!("%{}", name),
+                    Token::Derives => "::=".to_string(),
+                    Token::Alternative => "|".to_string(),
+                    Token::End => ";".to_string(),
+                    Token::Epsilon => "ε".to_string(),
+                };
+                self.error(pos, BnfErrorKind::Unexpected(Some(text)))
+            }
+            None => self.error(self.input.len(), BnfErrorKind::Unexpected(None)),
+        }
+    }
+
+    /// Creates an error at the given byte position.
+    fn error(&self, pos: usize, kind: BnfErrorKind) -> BnfError {
+        let before = &self.input[.. pos];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        BnfError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            kind: kind,
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -22,8 +22,9 @@ pub struct CycleParticipants<'a, G: 'a, R> {
     cycles: &'a Cycles<&'a mut G>,
 }
 
-/// Returns the set of symbols which participate in a cycle.
-fn unit_derivation_matrix<'a, G>(grammar: &'a G) -> FixedBitMatrix where
+/// Returns the transitive closure of unit derivations between symbols. A symbol participates in
+/// a cycle if it derives itself.
+pub fn unit_derivation_matrix<'a, G>(grammar: &'a G) -> FixedBitMatrix where
             G: ContextFree,
             &'a G: ContextFreeRef<'a, Target=G> {
     let num_syms = grammar.sym_source().num_syms();
@@ -0,0 +1,244 @@
+//! Enumeration and counting of sentences derived from a nonterminal.
+
+use std::cmp::{self, Ordering};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::f64;
+use std::u64;
+use std::usize;
+
+use grammar::{ContextFree, ContextFreeRef};
+use rule::GrammarRule;
+use symbol::GrammarSymbol;
+
+/// An iterator over sentences derived from a symbol, in the order of non-decreasing length.
+/// Symbols that have no rules are treated as terminals. A sentence is yielded once per leftmost
+/// derivation, so ambiguous grammars may yield the same sentence more than once.
+pub struct Derivations<S> where S: GrammarSymbol {
+    alternatives: BTreeMap<S, Vec<Vec<S>>>,
+    min_lengths: Vec<usize>,
+    queue: BinaryHeap<SententialForm<S>>,
+    order: usize,
+}
+
+/// A partially derived sentence. Its first `prefix` symbols are terminals.
+struct SententialForm<S> {
+    min_length: usize,
+    order: usize,
+    prefix: usize,
+    symbols: Vec<S>,
+}
+
+impl<S> PartialEq for SententialForm<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<S> Eq for SententialForm<S> {}
+
+impl<S> PartialOrd for SententialForm<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S> Ord for SententialForm<S> {
+    // Reversed, because `BinaryHeap` is a max-heap. Ties are broken in favor of the form that
+    // was queued first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.min_length, other.order).cmp(&(self.min_length, self.order))
+    }
+}
+
+/// Returns the length of the shortest sentence derived from each symbol, or `usize::MAX` for
+/// unproductive symbols.
+fn min_lengths<S>(alternatives: &BTreeMap<S, Vec<Vec<S>>>, num_syms: usize) -> Vec<usize> where
+            S: GrammarSymbol {
+    let mut min_lengths = vec![1; num_syms];
+    for &lhs in alternatives.keys() {
+        min_lengths[lhs.usize()] = usize::MAX;
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (&lhs, rhss) in alternatives {
+            for rhs in rhss {
+                let length = rhs_length(&min_lengths, rhs);
+                if length < min_lengths[lhs.usize()] {
+                    min_lengths[lhs.usize()] = length;
+                    changed = true;
+                }
+            }
+        }
+    }
+    min_lengths
+}
+
+fn rhs_length<S>(min_lengths: &[usize], rhs: &[S]) -> usize where S: GrammarSymbol {
+    rhs.iter().fold(0, |acc, sym| acc.saturating_add(min_lengths[sym.usize()]))
+}
+
+impl<S> Derivations<S> where S: GrammarSymbol {
+    /// Prepares the enumeration of sentences derived from `start`.
+    pub fn new<'a, G>(grammar: &'a G, start: S) -> Self where
+                G: ContextFree<Symbol=S>,
+                &'a G: ContextFreeRef<'a, Target=G> {
+        let mut alternatives = BTreeMap::new();
+        let mut num_syms = start.usize() + 1;
+        for rule in grammar.rules() {
+            alternatives.entry(rule.lhs()).or_insert_with(|| vec![]).push(rule.rhs().to_vec());
+            for sym in Some(rule.lhs()).into_iter().chain(rule.rhs().iter().cloned()) {
+                num_syms = cmp::max(num_syms, sym.usize() + 1);
+            }
+        }
+        let min_lengths = min_lengths(&alternatives, num_syms);
+        let mut derivations = Derivations {
+            alternatives: alternatives,
+            min_lengths: min_lengths,
+            queue: BinaryHeap::new(),
+            order: 0,
+        };
+        derivations.push(0, vec![start]);
+        derivations
+    }
+
+    fn push(&mut self, prefix: usize, symbols: Vec<S>) {
+        let min_length = rhs_length(&self.min_lengths, &symbols);
+        if min_length == usize::MAX {
+            return;
+        }
+        self.queue.push(SententialForm {
+            min_length: min_length,
+            order: self.order,
+            prefix: prefix,
+            symbols: symbols,
+        });
+        self.order += 1;
+    }
+}
+
+impl<S> Iterator for Derivations<S> where S: GrammarSymbol {
+    type Item = Vec<S>;
+
+    fn next(&mut self) -> Option<Vec<S>> {
+        while let Some(form) = self.queue.pop() {
+            let SententialForm { prefix, symbols, .. } = form;
+            let position = symbols[prefix..].iter().position(|sym| {
+                self.alternatives.contains_key(sym)
+            });
+            let pos = match position {
+                Some(offset) => prefix + offset,
+                None => return Some(symbols),
+            };
+            let rhss = self.alternatives[&symbols[pos]].clone();
+            for rhs in rhss {
+                let mut expanded = symbols[..pos].to_vec();
+                expanded.extend(rhs);
+                expanded.extend(symbols[pos + 1..].iter().cloned());
+                self.push(pos, expanded);
+            }
+        }
+        None
+    }
+}
+
+/// Returns the number of derivations of sentences of each length up to `max_len` from `start`,
+/// saturating at `u64::MAX`. For unambiguous grammars, these are the numbers of distinct
+/// sentences. Symbols that have no rules are treated as terminals.
+pub fn sentence_counts<'a, G>(grammar: &'a G, start: G::Symbol, max_len: usize) -> Vec<u64> where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G> {
+    let mut rules: Vec<(usize, Vec<usize>)> = vec![];
+    let mut num_syms = start.usize() + 1;
+    for rule in grammar.rules() {
+        rules.push((rule.lhs().usize(), rule.rhs().iter().map(|sym| sym.usize()).collect()));
+        for sym in Some(rule.lhs()).into_iter().chain(rule.rhs().iter().cloned()) {
+            num_syms = cmp::max(num_syms, sym.usize() + 1);
+        }
+    }
+    let mut nonterminal = vec![false; num_syms];
+    for &(lhs, _) in &rules {
+        nonterminal[lhs] = true;
+    }
+    let num_nonterminals = nonterminal.iter().filter(|&&nonterminal| nonterminal).count();
+
+    // counts[sym][len]
+    let mut counts = vec![vec![0u64; max_len + 1]; num_syms];
+    for (sym, counts) in counts.iter_mut().enumerate() {
+        if !nonterminal[sym] && max_len >= 1 {
+            counts[1] = 1;
+        }
+    }
+    for len in 0 .. max_len + 1 {
+        // Without cycles, counts for this length settle after one round per nonterminal.
+        // Counts that are still growing come from cycles, and are infinite.
+        let mut rounds = 0;
+        loop {
+            let mut new_counts = vec![0u64; num_syms];
+            for &(lhs, ref rhs) in &rules {
+                let count = rhs_counts(&counts, rhs, len);
+                new_counts[lhs] = new_counts[lhs].saturating_add(count);
+            }
+            let mut changed = false;
+            for sym in 0 .. num_syms {
+                if nonterminal[sym] && new_counts[sym] != counts[sym][len] {
+                    changed = true;
+                    counts[sym][len] = if rounds > num_nonterminals {
+                        u64::MAX
+                    } else {
+                        new_counts[sym]
+                    };
+                }
+            }
+            if !changed {
+                break;
+            }
+            rounds += 1;
+        }
+    }
+    counts.swap_remove(start.usize())
+}
+
+/// Returns the number of ways in which a string of symbols derives sentences of length `len`.
+fn rhs_counts(counts: &[Vec<u64>], rhs: &[usize], len: usize) -> u64 {
+    let mut ways = vec![0u64; len + 1];
+    ways[0] = 1;
+    for &sym in rhs {
+        let mut next = vec![0u64; len + 1];
+        for prefix_len in 0 .. len + 1 {
+            if ways[prefix_len] == 0 {
+                continue;
+            }
+            for sym_len in 0 .. len + 1 - prefix_len {
+                let product = ways[prefix_len].saturating_mul(counts[sym][sym_len]);
+                next[prefix_len + sym_len] = next[prefix_len + sym_len].saturating_add(product);
+            }
+        }
+        ways = next;
+    }
+    ways[len]
+}
+
+/// Returns the fraction of sentences up to `max_len` long that have two or more derivations.
+/// Returns `f64::INFINITY` if the number of derivations saturates, as it does for grammars with
+/// cycles, because such derivations can't be enumerated.
+pub fn ambiguity_rate<'a, G>(grammar: &'a G, start: G::Symbol, max_len: usize) -> f64 where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G> {
+    // Derivations are enumerated shortest first, so these are all derivations up to `max_len`.
+    let num_derivations = sentence_counts(grammar, start, max_len).iter().fold(0, |acc: u64, &n| {
+        acc.saturating_add(n)
+    });
+    if num_derivations == u64::MAX {
+        return f64::INFINITY;
+    }
+    let mut derivations = HashMap::new();
+    for sentence in Derivations::new(grammar, start).take(num_derivations as usize) {
+        *derivations.entry(sentence).or_insert(0) += 1;
+    }
+    if derivations.is_empty() {
+        return 0.0;
+    }
+    let ambiguous = derivations.values().filter(|&&count| count >= 2).count();
+    ambiguous as f64 / derivations.len() as f64
+}
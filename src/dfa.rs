@@ -0,0 +1,266 @@
+//! Deterministic finite automata for regular parts of grammars.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use grammar::{ContextFree, ContextFreeRef};
+use rule::GrammarRule;
+use symbol::GrammarSymbol;
+
+/// A minimal deterministic finite automaton. Transitions that are missing lead to rejection.
+/// The initial state is 0.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dfa<S> {
+    transitions: Vec<BTreeMap<S, usize>>,
+    accepting: Vec<bool>,
+}
+
+/// A nondeterministic automaton with transitions on `None` as epsilon transitions.
+struct Nfa<S> {
+    transitions: Vec<Vec<(Option<S>, usize)>>,
+}
+
+impl<S> Nfa<S> where S: GrammarSymbol {
+    fn new(num_states: usize) -> Self {
+        Nfa { transitions: (0 .. num_states).map(|_| vec![]).collect() }
+    }
+
+    /// Adds a path of transitions on the given symbols.
+    fn add_path(&mut self, from: usize, syms: &[S], to: usize) {
+        let mut state = from;
+        for (i, &sym) in syms.iter().enumerate() {
+            let next = if i + 1 == syms.len() {
+                to
+            } else {
+                self.transitions.push(vec![]);
+                self.transitions.len() - 1
+            };
+            self.transitions[state].push((Some(sym), next));
+            state = next;
+        }
+        if syms.is_empty() {
+            self.transitions[from].push((None, to));
+        }
+    }
+
+    fn epsilon_closure(&self, states: &mut BTreeSet<usize>) {
+        let mut work: Vec<_> = states.iter().cloned().collect();
+        while let Some(state) = work.pop() {
+            for &(sym, next) in &self.transitions[state] {
+                if sym.is_none() && states.insert(next) {
+                    work.push(next);
+                }
+            }
+        }
+    }
+
+    /// The subset construction.
+    fn determinize(&self, initial: usize, accepting: usize) -> Dfa<S> {
+        let mut start = BTreeSet::new();
+        start.insert(initial);
+        self.epsilon_closure(&mut start);
+        let mut subsets = vec![start.clone()];
+        let mut ids = BTreeMap::new();
+        ids.insert(start, 0);
+        let mut dfa = Dfa { transitions: vec![], accepting: vec![] };
+        let mut i = 0;
+        while i < subsets.len() {
+            let mut targets: BTreeMap<S, BTreeSet<usize>> = BTreeMap::new();
+            for &state in &subsets[i] {
+                for &(sym, next) in &self.transitions[state] {
+                    if let Some(sym) = sym {
+                        targets.entry(sym).or_insert_with(|| BTreeSet::new()).insert(next);
+                    }
+                }
+            }
+            let mut transitions = BTreeMap::new();
+            for (sym, mut target) in targets {
+                self.epsilon_closure(&mut target);
+                let id = match ids.get(&target) {
+                    Some(&id) => id,
+                    None => {
+                        subsets.push(target.clone());
+                        subsets.len() - 1
+                    }
+                };
+                ids.insert(target, id);
+                transitions.insert(sym, id);
+            }
+            dfa.transitions.push(transitions);
+            dfa.accepting.push(subsets[i].contains(&accepting));
+            i += 1;
+        }
+        dfa
+    }
+}
+
+impl<S> Dfa<S> where S: GrammarSymbol {
+    /// Builds the automaton for the language derived from `start`, if every rule reachable from
+    /// `start` is right-linear, or every such rule is left-linear. Returns `None` otherwise.
+    /// Symbols that have no rules are treated as terminals.
+    pub fn from_grammar<'a, G>(grammar: &'a G, start: S) -> Option<Self> where
+                G: ContextFree<Symbol=S>,
+                &'a G: ContextFreeRef<'a, Target=G> {
+        let mut alternatives = BTreeMap::new();
+        for rule in grammar.rules() {
+            alternatives.entry(rule.lhs()).or_insert_with(|| vec![]).push(rule.rhs().to_vec());
+        }
+        if !alternatives.contains_key(&start) {
+            return None;
+        }
+        // Nonterminals reachable from `start`, numbered in the order of discovery.
+        let mut states = BTreeMap::new();
+        states.insert(start, 0);
+        let mut reachable = vec![start];
+        let mut i = 0;
+        while i < reachable.len() {
+            for rhs in &alternatives[&reachable[i]] {
+                for &sym in rhs.iter() {
+                    if alternatives.contains_key(&sym) && !states.contains_key(&sym) {
+                        states.insert(sym, reachable.len());
+                        reachable.push(sym);
+                    }
+                }
+            }
+            i += 1;
+        }
+        let is_terminal = |sym: &S| !alternatives.contains_key(sym);
+        let mut rules = vec![];
+        for &lhs in &reachable {
+            for rhs in &alternatives[&lhs] {
+                rules.push((lhs, &rhs[..]));
+            }
+        }
+        let right_linear = rules.iter().all(|&(_, rhs)| {
+            rhs.len() < 2 || rhs[.. rhs.len() - 1].iter().all(|sym| is_terminal(sym))
+        });
+        let left_linear = rules.iter().all(|&(_, rhs)| {
+            rhs.len() < 2 || rhs[1 ..].iter().all(|sym| is_terminal(sym))
+        });
+
+        // One state for each nonterminal, and one extra state.
+        let extra = reachable.len();
+        let mut nfa = Nfa::new(extra + 1);
+        let dfa = if right_linear {
+            // A ::= w B is a path from A to B, and A ::= w is a path from A to the final state.
+            for (lhs, rhs) in rules {
+                match rhs.last() {
+                    Some(sym) if !is_terminal(sym) => {
+                        nfa.add_path(states[&lhs], &rhs[.. rhs.len() - 1], states[sym]);
+                    }
+                    _ => nfa.add_path(states[&lhs], rhs, extra),
+                }
+            }
+            nfa.determinize(0, extra)
+        } else if left_linear {
+            // A ::= B w is a path from B to A, and A ::= w is a path from the initial state to A.
+            for (lhs, rhs) in rules {
+                match rhs.first() {
+                    Some(sym) if !is_terminal(sym) => {
+                        nfa.add_path(states[sym], &rhs[1 ..], states[&lhs]);
+                    }
+                    _ => nfa.add_path(extra, rhs, states[&lhs]),
+                }
+            }
+            nfa.determinize(extra, 0)
+        } else {
+            return None;
+        };
+        Some(dfa.minimize())
+    }
+
+    /// Removes states from which no accepting state is reachable, and merges equivalent states.
+    fn minimize(self) -> Self {
+        let num_states = self.accepting.len();
+        let mut live = self.accepting.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for state in 0 .. num_states {
+                if !live[state] && self.transitions[state].values().any(|&next| live[next]) {
+                    live[state] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        // Partition refinement. Dead states are left out of all classes.
+        let mut class: Vec<Option<usize>> = (0 .. num_states).map(|state| {
+            if live[state] { Some(self.accepting[state] as usize) } else { None }
+        }).collect();
+        let mut num_classes = 0;
+        loop {
+            let mut signatures = BTreeMap::new();
+            let mut next_class = vec![None; num_states];
+            for state in 0 .. num_states {
+                if let Some(own) = class[state] {
+                    let edges: Vec<_> = self.transitions[state].iter().filter_map(|(&sym, &next)| {
+                        class[next].map(|next_class| (sym, next_class))
+                    }).collect();
+                    let len = signatures.len();
+                    let id = *signatures.entry((own, edges)).or_insert(len);
+                    next_class[state] = Some(id);
+                }
+            }
+            class = next_class;
+            if signatures.len() == num_classes {
+                break;
+            }
+            num_classes = signatures.len();
+        }
+
+        // Renumber the classes so that the initial state stays first.
+        let mut order = BTreeMap::new();
+        if class[0].is_none() {
+            return Dfa { transitions: vec![BTreeMap::new()], accepting: vec![false] };
+        }
+        for state in 0 .. num_states {
+            if let Some(id) = class[state] {
+                let len = order.len();
+                order.entry(id).or_insert(len);
+            }
+        }
+        let mut dfa = Dfa {
+            transitions: vec![BTreeMap::new(); order.len()],
+            accepting: vec![false; order.len()],
+        };
+        for state in 0 .. num_states {
+            if let Some(id) = class[state] {
+                let new = order[&id];
+                dfa.accepting[new] = self.accepting[state];
+                for (&sym, &next) in &self.transitions[state] {
+                    if let Some(next_id) = class[next] {
+                        dfa.transitions[new].insert(sym, order[&next_id]);
+                    }
+                }
+            }
+        }
+        dfa
+    }
+
+    /// Returns the number of states.
+    pub fn num_states(&self) -> usize {
+        self.accepting.len()
+    }
+
+    /// Returns the state reached from `state` on `sym`, if any.
+    pub fn transition(&self, state: usize, sym: S) -> Option<usize> {
+        self.transitions[state].get(&sym).cloned()
+    }
+
+    /// Checks whether the state is accepting.
+    pub fn is_accepting(&self, state: usize) -> bool {
+        self.accepting[state]
+    }
+
+    /// Checks whether the automaton accepts the input.
+    pub fn accepts(&self, input: &[S]) -> bool {
+        let mut state = 0;
+        for &sym in input {
+            match self.transition(state, sym) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        self.accepting[state]
+    }
+}
@@ -0,0 +1,255 @@
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use grammar::{ContextFree, ContextFreeRef};
+use rule::GrammarRule;
+use sequence::{Separator, Sequence};
+use symbol::GrammarSymbol;
+
+/// Writes the grammar's rules in BNF. Rules are grouped by their LHS, in the order of symbol IDs.
+/// Alternatives with the same LHS are in the grammar's order.
+pub fn write_bnf<'a, G, W>(grammar: &'a G, w: &mut W) -> fmt::Result where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G>,
+            W: fmt::Write {
+    let mut alternatives = BTreeMap::new();
+    for rule in grammar.rules() {
+        alternatives.entry(rule.lhs()).or_insert_with(|| vec![]).push(rule.rhs().to_vec());
+    }
+    for (lhs, rhss) in alternatives {
+        try!(write!(w, "s{} ::=", lhs.usize()));
+        for (i, rhs) in rhss.iter().enumerate() {
+            if i != 0 {
+                try!(write!(w, " |"));
+            }
+            if rhs.is_empty() {
+                try!(write!(w, " ε"));
+            }
+            for sym in rhs {
+                try!(write!(w, " s{}", sym.usize()));
+            }
+        }
+        try!(writeln!(w, ";"));
+    }
+    Ok(())
+}
+
+/// Writes the grammar's rules as a PEG. Rules are grouped by their LHS, in the order of symbol
+/// IDs. Alternatives with the same LHS become ordered choices, in the grammar's order.
+pub fn write_peg<'a, G, W>(grammar: &'a G, w: &mut W) -> fmt::Result where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G>,
+            W: fmt::Write {
+    let mut alternatives = BTreeMap::new();
+    for rule in grammar.rules() {
+        alternatives.entry(rule.lhs()).or_insert_with(|| vec![]).push(rule.rhs().to_vec());
+    }
+    for (lhs, rhss) in alternatives {
+        try!(write!(w, "s{} <-", lhs.usize()));
+        for (i, rhs) in rhss.iter().enumerate() {
+            if i != 0 {
+                try!(write!(w, " /"));
+            }
+            if rhs.is_empty() {
+                try!(write!(w, " \"\""));
+            }
+            for sym in rhs {
+                try!(write!(w, " s{}", sym.usize()));
+            }
+        }
+        try!(writeln!(w, ""));
+    }
+    Ok(())
+}
+
+/// Writes a graph in the DOT language, with an edge from every rule's LHS to each symbol on
+/// its RHS. Nodes and edges are in the order of symbol IDs.
+pub fn write_dot<'a, G, W>(grammar: &'a G, w: &mut W) -> fmt::Result where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G>,
+            W: fmt::Write {
+    let mut nodes = BTreeSet::new();
+    let mut edges = BTreeSet::new();
+    for rule in grammar.rules() {
+        nodes.insert(rule.lhs().usize());
+        for sym in rule.rhs() {
+            nodes.insert(sym.usize());
+            edges.insert((rule.lhs().usize(), sym.usize()));
+        }
+    }
+    try!(writeln!(w, "digraph {{"));
+    for node in nodes {
+        try!(writeln!(w, "    s{};", node));
+    }
+    for (from, to) in edges {
+        try!(writeln!(w, "    s{} -> s{};", from, to));
+    }
+    writeln!(w, "}}")
+}
+
+/// Writes rules as a graph in the DOT language, with an edge from every rule's LHS to each
+/// symbol on its RHS. Nodes are labeled with `label(sym)`. Symbols without a label, such as
+/// helpers allocated by rewrites, are drawn dashed and labeled with `s` followed by their IDs.
+/// Nodes and edges are in the order of symbol IDs.
+pub fn write_rules_dot<I, F, W>(rules: I, label: F, w: &mut W) -> fmt::Result where
+            I: IntoIterator,
+            I::Item: GrammarRule,
+            <I::Item as GrammarRule>::Symbol: GrammarSymbol,
+            F: Fn(<I::Item as GrammarRule>::Symbol) -> Option<String>,
+            W: fmt::Write {
+    let mut nodes = BTreeSet::new();
+    let mut edges = BTreeSet::new();
+    for rule in rules {
+        nodes.insert(rule.lhs());
+        for &sym in rule.rhs() {
+            nodes.insert(sym);
+            edges.insert((rule.lhs().usize(), sym.usize()));
+        }
+    }
+    try!(writeln!(w, "digraph {{"));
+    for node in nodes {
+        match label(node) {
+            Some(label) => {
+                let label = label.replace("\\", "\\\\").replace("\"", "\\\"");
+                try!(writeln!(w, "    s{} [label=\"{}\"];", node.usize(), label));
+            }
+            None => try!(writeln!(w, "    s{} [style=dashed];", node.usize())),
+        }
+    }
+    for (from, to) in edges {
+        try!(writeln!(w, "    s{} -> s{};", from, to));
+    }
+    writeln!(w, "}}")
+}
+
+/// Writes rules in the syntax of Lark. Sequence rules are written as repetitions. Symbols that
+/// have rules are written in lowercase, and terminals in uppercase. Terminals are declared with
+/// `%declare`, as they have no definitions. Symbols without a name are written as `s` followed
+/// by their IDs. Characters that Lark doesn't allow in names are replaced with `_`, and names
+/// that are already taken get a numeric suffix, such as `COMMA_2`. Empty alternatives, which
+/// include sequences of at most zero items, are only written for symbols that derive nothing
+/// else. Otherwise, the other alternatives are grouped and made optional, as in `(a | b)?`.
+pub fn write_lark<I, Hs, W>(rules: I,
+                            sequences: &[Sequence<Hs, <I::Item as GrammarRule>::Symbol>],
+                            names: &BTreeMap<<I::Item as GrammarRule>::Symbol, String>,
+                            w: &mut W) -> fmt::Result where
+            I: IntoIterator,
+            I::Item: GrammarRule,
+            <I::Item as GrammarRule>::Symbol: GrammarSymbol,
+            W: fmt::Write {
+    let rules = rules.into_iter().collect::<Vec<_>>();
+    let mut defined = BTreeSet::new();
+    defined.extend(rules.iter().map(|rule| rule.lhs()));
+    defined.extend(sequences.iter().map(|seq| seq.lhs));
+    let mut syms = defined.clone();
+    for rule in &rules {
+        syms.extend(rule.rhs().iter().cloned());
+    }
+    for seq in sequences {
+        syms.extend(Some(seq.rhs).into_iter().chain(seq.separator.symbol()));
+    }
+    let mut unique_names = BTreeMap::new();
+    let mut taken = BTreeSet::new();
+    for &sym in &syms {
+        let name = names.get(&sym).cloned().unwrap_or_else(|| format!("s{}", sym.usize()));
+        let base = lark_name(&name, defined.contains(&sym));
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while !taken.insert(name.clone()) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        unique_names.insert(sym, name);
+    }
+    let name = |sym| unique_names[&sym].clone();
+
+    let mut alternatives = BTreeMap::new();
+    for rule in &rules {
+        let rhs = rule.rhs().iter().map(|&sym| name(sym)).collect::<Vec<_>>();
+        alternatives.entry(rule.lhs()).or_insert_with(|| vec![]).push(rhs.join(" "));
+    }
+    for seq in sequences {
+        let expansion = lark_sequence(seq, &name);
+        alternatives.entry(seq.lhs).or_insert_with(|| vec![]).push(expansion);
+    }
+
+    for (lhs, expansions) in alternatives {
+        let has_empty = expansions.iter().any(|expansion| expansion.is_empty());
+        let nonempty = expansions.into_iter().filter(|expansion| !expansion.is_empty());
+        let body = nonempty.collect::<Vec<_>>().join(" | ");
+        if has_empty && !body.is_empty() {
+            try!(writeln!(w, "{}: ({})?", name(lhs), body));
+        } else {
+            try!(writeln!(w, "{}: {}", name(lhs), body));
+        }
+    }
+    let terminals = syms.iter().filter(|sym| !defined.contains(sym));
+    let terminals = terminals.map(|&sym| name(sym)).collect::<Vec<_>>();
+    if !terminals.is_empty() {
+        try!(writeln!(w, "%declare {}", terminals.join(" ")));
+    }
+    Ok(())
+}
+
+/// Turns a name into the name of a Lark rule or terminal. Names start with a letter, because a
+/// leading underscore has a meaning in Lark.
+fn lark_name(name: &str, is_rule: bool) -> String {
+    let mut sanitized = name.chars().map(|c| {
+        match c {
+            'a'...'z' | 'A'...'Z' | '0'...'9' => c,
+            _ => '_',
+        }
+    }).collect::<String>();
+    match sanitized.chars().next() {
+        Some('a'...'z') | Some('A'...'Z') => {}
+        _ => sanitized.insert(0, 's'),
+    }
+    if is_rule { sanitized.to_lowercase() } else { sanitized.to_uppercase() }
+}
+
+fn lark_sequence<H, S, F>(seq: &Sequence<H, S>, name: &F) -> String where
+            S: GrammarSymbol,
+            F: Fn(S) -> String {
+    let item = name(seq.rhs);
+    let (start, end) = (seq.start, seq.end);
+    if end == Some(0) {
+        return String::new();
+    }
+    let mut parts = vec![];
+    match seq.separator {
+        Separator::Null => return lark_repeat(item, start, end),
+        Separator::Trailing(sep) => {
+            parts.push(lark_repeat(format!("({} {})", item, name(sep)), cmp::max(start, 1), end));
+        }
+        Separator::Proper(sep) | Separator::Liberal(sep) => {
+            let rest = lark_repeat(format!("({} {})", name(sep), item),
+                                   start.saturating_sub(1),
+                                   end.map(|n| n - 1));
+            parts.push(item);
+            parts.push(rest);
+            if let Separator::Liberal(_) = seq.separator {
+                parts.push(format!("{}?", name(sep)));
+            }
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    let body = parts.join(" ");
+    if start == 0 {
+        format!("({})?", body)
+    } else {
+        body
+    }
+}
+
+fn lark_repeat(atom: String, min: u32, max: Option<u32>) -> String {
+    match (min, max) {
+        (_, Some(0)) => String::new(),
+        (0, None) => format!("{}*", atom),
+        (1, None) => format!("{}+", atom),
+        (0, Some(1)) => format!("{}?", atom),
+        (min, None) => format!("{} ~ {} {}*", atom, min, atom),
+        (min, Some(max)) if min == max => format!("{} ~ {}", atom, min),
+        (min, Some(max)) => format!("{} ~ {}..{}", atom, min, max),
+    }
+}
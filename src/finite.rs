@@ -0,0 +1,80 @@
+use bit_matrix::FixedBitMatrix;
+use bit_vec::BitVec;
+
+use grammar::{ContextFree, ContextFreeRef};
+use rhs_closure::RhsClosure;
+use rule::GrammarRule;
+use symbol::{SymbolSource, GrammarSymbol, SymbolBitSet};
+
+/// Returns the set of nonterminals that derive finite languages.
+pub fn finite_syms<'a, G>(grammar: &'a G) -> SymbolBitSet<G::Symbol> where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G> {
+    let num_syms = grammar.sym_source().num_syms();
+    let mut nonterminal = BitVec::from_elem(num_syms, false);
+    for rule in grammar.rules() {
+        nonterminal.set(rule.lhs().usize(), true);
+    }
+
+    let mut productive = nonterminal.clone();
+    productive.negate();
+    for rule in grammar.rules() {
+        if rule.rhs().is_empty() {
+            productive.set(rule.lhs().usize(), true);
+        }
+    }
+    RhsClosure::new(grammar).rhs_closure(&mut productive);
+
+    // Symbols that derive at least one nonempty string.
+    let mut nonempty = nonterminal.clone();
+    nonempty.negate();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in grammar.rules() {
+            if !nonempty[rule.lhs().usize()]
+                    && rule.rhs().iter().all(|sym| productive[sym.usize()])
+                    && rule.rhs().iter().any(|sym| nonempty[sym.usize()]) {
+                nonempty.set(rule.lhs().usize(), true);
+                changed = true;
+            }
+        }
+    }
+
+    // A nonterminal `A` derives `B` if `B` occurs in a productive rule for `A`. The derivation
+    // pumps if the rest of the rule's RHS can derive a nonempty string.
+    let mut derivation = FixedBitMatrix::new(num_syms, num_syms);
+    let mut pumping = vec![];
+    for rule in grammar.rules() {
+        if !rule.rhs().iter().all(|sym| productive[sym.usize()]) {
+            continue;
+        }
+        let lhs = rule.lhs().usize();
+        for (i, sym) in rule.rhs().iter().enumerate() {
+            let sym = sym.usize();
+            if nonterminal[sym] {
+                derivation.set(lhs, sym, true);
+                let rest_nonempty = rule.rhs().iter().enumerate().any(|(j, other)| {
+                    j != i && nonempty[other.usize()]
+                });
+                if rest_nonempty {
+                    pumping.push((lhs, sym));
+                }
+            }
+        }
+    }
+    derivation.transitive_closure();
+
+    // The language of a symbol is infinite if the symbol derives a pumping cycle.
+    let mut finite = nonterminal;
+    for (lhs, sym) in pumping {
+        if lhs == sym || derivation[(sym, lhs)] {
+            for i in 0 .. num_syms {
+                if i == lhs || derivation[(i, lhs)] {
+                    finite.set(i, false);
+                }
+            }
+        }
+    }
+    SymbolBitSet::from_bit_vec(finite)
+}
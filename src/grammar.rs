@@ -1,17 +1,40 @@
+use collections::range::RangeArgument;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::collections::hash_map::Entry;
+use std::fmt;
 use std::mem;
 use std::ops::Deref;
 use std::slice;
+use std::vec;
+
+#[cfg(feature = "petgraph")]
+use petgraph::Graph;
+#[cfg(feature = "petgraph")]
+use petgraph::graph::NodeIndex;
 
 use binarized::BinarizedCfg;
-use history::{Binarize, AssignPrecedence, RewriteSequence, NullHistory, Action};
+use export::{write_bnf, write_dot, write_lark, write_peg};
+use derivation::{ambiguity_rate, sentence_counts, Derivations};
+use dfa::Dfa;
+use finite::finite_syms;
+#[cfg(feature = "petgraph")]
+use graph::to_petgraph;
+use history::{Binarize, AssignPrecedence, RewriteSequence, NullHistory, Action, Weighted};
+use mandatory::mandatory_terminals;
+use nullable::{nullable_syms, nullable_left_recursive_syms};
 use precedence::PrecedencedRuleBuilder;
+use prediction::{predict_sets, FirstSets, Ll1Error, Ll1Table};
+use recognizer::cyk_recognize;
 use rule::{GrammarRule, Rule};
 use rule_builder::RuleBuilder;
 use rule_container::RuleContainer;
-use sequence::Sequence;
+use sequence::{LeftRecGroup, Recursion, Separator, Sequence};
 use sequence_builder::SequenceRuleBuilder;
-use sequence_destination::SequencesToProductions;
-use symbol::{ConsecutiveSymbols, SymbolSource, GrammarSymbol, TerminalSymbolSet};
+use sequence_destination::{SequenceDestination, SequencesToProductions};
+use symbol::{ByteSymbols, ConsecutiveSymbols, NumericSymbol, SymbolSource, GrammarSymbol};
+use symbol::{SymbolBitSet, SymbolKind, SymbolMap, TerminalSymbolSet};
+use usefulness::HealthReport;
 
 /// Trait for context-free grammars.
 pub trait ContextFree: RuleContainer + Sized {
@@ -39,6 +62,325 @@ pub trait ContextFree: RuleContainer + Sized {
                 Self::Source: Clone {
         BinarizedCfg::from_context_free(self)
     }
+
+    /// Checks whether the grammar derives the empty string from `start`.
+    fn accepts_empty<'a>(&'a self, start: Self::Symbol) -> bool where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        nullable_syms(self)[start.usize()]
+    }
+
+    /// Returns the nonterminals that are both nullable and left-recursive, directly or through
+    /// other symbols. Naive top-down parsers may loop forever on such symbols.
+    fn nullable_left_recursion<'a>(&'a self) -> Vec<Self::Symbol> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        nullable_left_recursive_syms(self)
+    }
+
+    /// Returns the grammar's start symbols. By default, that's only the start symbol of the
+    /// symbol source.
+    fn start_symbols(&self) -> Vec<Self::Symbol> {
+        vec![self.sym_source().start_sym()]
+    }
+
+    /// Returns an iterator over sentences derived from `start`, shortest first. Symbols that have
+    /// no rules are treated as terminals.
+    fn derivations<'a>(&'a self, start: Self::Symbol) -> Derivations<Self::Symbol> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        Derivations::new(self, start)
+    }
+
+    /// Returns the number of sentences of each length from 0 to `max_len` derived from `start`,
+    /// saturating on overflow. Sentences are counted once per derivation, so the counts are
+    /// exact for unambiguous grammars. Symbols that have no rules are treated as terminals.
+    fn sentence_counts_up_to<'a>(&'a self, start: Self::Symbol, max_len: usize) -> Vec<u64> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        sentence_counts(self, start, max_len)
+    }
+
+    /// Returns the fraction of distinct sentences up to `max_len` long derived from `start` that
+    /// have two or more derivations, or 0.0 if there are no such sentences. Sentences are
+    /// enumerated, so this is practical only for small lengths. Returns `f64::INFINITY` for
+    /// grammars with cycles, which have infinitely many derivations.
+    fn ambiguity_rate<'a>(&'a self, start: Self::Symbol, max_len: usize) -> f64 where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        ambiguity_rate(self, start, max_len)
+    }
+
+    /// Checks whether `start` derives `input` with the CYK algorithm. The grammar must be in
+    /// Chomsky normal form, which is checked in debug builds. See `recognizer::cyk_recognize`.
+    fn cyk_recognizes<'a>(&'a self, start: Self::Symbol, input: &[Self::Symbol]) -> bool where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        cyk_recognize(self, start, input)
+    }
+
+    /// Returns a minimal DFA for the language derived from `start`, if the grammar rooted at
+    /// `start` is right-linear or left-linear over terminals. Symbols that have no rules are
+    /// treated as terminals.
+    fn to_dfa<'a>(&'a self, start: Self::Symbol) -> Option<Dfa<Self::Symbol>> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        Dfa::from_grammar(self, start)
+    }
+
+    /// Returns the set of terminals that occur in every sentence derived from `start`, such as
+    /// the required parts of a record. Symbols that have no rules are treated as terminals.
+    fn mandatory_terminals<'a>(&'a self, start: Self::Symbol) -> SymbolBitSet<Self::Symbol> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        mandatory_terminals(self, start)
+    }
+
+    /// Returns the set of nonterminals that derive finite languages.
+    fn finite_symbols<'a>(&'a self) -> SymbolBitSet<Self::Symbol> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        finite_syms(self)
+    }
+
+    /// Returns the set of symbols that appear on the RHS of some rule, but neither have rules nor
+    /// are classified as terminals. Such symbols are often the result of a typo.
+    fn undefined_symbols<'a>(&'a self) -> SymbolBitSet<Self::Symbol> where
+                Self: TerminalSymbolSet,
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        let num_syms = self.sym_source().num_syms();
+        let mut defined = SymbolBitSet::new(num_syms);
+        let mut undefined = SymbolBitSet::new(num_syms);
+        for rule in self.rules() {
+            defined.insert(rule.lhs());
+        }
+        for rule in self.rules() {
+            for &sym in rule.rhs() {
+                if !defined.contains(sym) && !self.is_terminal(sym) {
+                    undefined.insert(sym);
+                }
+            }
+        }
+        undefined
+    }
+
+    /// Checks the grammar rooted at `start` for unreachable, unproductive and undefined symbols,
+    /// cycles and nullable left recursion. See `usefulness::HealthReport`.
+    fn health_report<'a>(&'a self, start: Self::Symbol) -> HealthReport<Self::Symbol> where
+                Self: TerminalSymbolSet,
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        HealthReport::new(self, start)
+    }
+
+    /// Returns the set of nonterminals that derive their languages through right-linear rules
+    /// only. The RHS of a right-linear rule consists of terminals, optionally followed by a
+    /// nonterminal. Symbols that aren't on the LHS of any rule are terminals.
+    fn right_linear_symbols<'a>(&'a self) -> SymbolBitSet<Self::Symbol> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        let num_syms = self.sym_source().num_syms();
+        let mut nonterminals = SymbolBitSet::new(num_syms);
+        for rule in self.rules() {
+            nonterminals.insert(rule.lhs());
+        }
+        let mut right_linear = nonterminals.clone();
+        for rule in self.rules() {
+            let init = &rule.rhs()[.. cmp::max(rule.rhs().len(), 1) - 1];
+            if init.iter().any(|&sym| nonterminals.contains(sym)) {
+                right_linear.remove(rule.lhs());
+            }
+        }
+        // Nonterminals that appear last on the RHS must be right-linear, too.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in self.rules() {
+                if let Some(&last) = rule.rhs().last() {
+                    if right_linear.contains(rule.lhs())
+                            && nonterminals.contains(last)
+                            && !right_linear.contains(last) {
+                        right_linear.remove(rule.lhs());
+                        changed = true;
+                    }
+                }
+            }
+        }
+        right_linear
+    }
+
+    /// Returns pairs of a nonterminal and a symbol, where the nonterminal derives either nothing
+    /// or the symbol. In EBNF, such nonterminals are written as an optional symbol.
+    fn mark_optionals<'a>(&'a self) -> Vec<(Self::Symbol, Self::Symbol)> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        let mut alternatives = BTreeMap::new();
+        for rule in self.rules() {
+            alternatives.entry(rule.lhs()).or_insert_with(|| vec![]).push(rule.rhs().to_vec());
+        }
+        alternatives.into_iter().filter_map(|(lhs, rhss)| {
+            if rhss.len() != 2 {
+                return None;
+            }
+            let (first, second) = (&rhss[0], &rhss[1]);
+            match (first.len(), second.len()) {
+                (0, 1) => Some((lhs, second[0])),
+                (1, 0) => Some((lhs, first[0])),
+                _ => None
+            }
+        }).collect()
+    }
+
+    /// Returns the indices of rules that have some symbol more than once on their RHS.
+    fn rules_with_repeats<'a>(&'a self) -> Vec<usize> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        self.rules().enumerate().filter(|&(_, rule)| {
+            let rhs = rule.rhs();
+            rhs.iter().enumerate().any(|(i, sym)| rhs[i + 1 ..].contains(sym))
+        }).map(|(idx, _)| idx).collect()
+    }
+
+    /// Returns the start symbols and all symbols that occur in rules, sorted, together with
+    /// their kinds. Rules generated by rewrites are recognized with `Action::is_generated`.
+    /// Start symbols are never classified as generated.
+    fn symbols<'a>(&'a self) -> vec::IntoIter<(Self::Symbol, SymbolKind)> where
+                &'a Self: ContextFreeRef<'a, Target=Self>,
+                Self::History: Action {
+        let mut kinds = BTreeMap::new();
+        for sym in self.start_symbols() {
+            kinds.insert(sym, SymbolKind::Terminal);
+        }
+        for rule in self.rules() {
+            for &sym in rule.rhs() {
+                kinds.entry(sym).or_insert(SymbolKind::Terminal);
+            }
+        }
+        for rule in self.rules() {
+            let kind = kinds.entry(rule.lhs()).or_insert(SymbolKind::Terminal);
+            if *kind == SymbolKind::Terminal {
+                *kind = SymbolKind::Generated;
+            }
+            if !rule.history().is_generated() {
+                *kind = SymbolKind::Nonterminal;
+            }
+        }
+        // Start symbols and symbols that occur in rules that weren't generated aren't helpers.
+        let mut roots = self.start_symbols();
+        for rule in self.rules() {
+            if !rule.history().is_generated() {
+                roots.extend(rule.rhs().iter().cloned());
+            }
+        }
+        for sym in roots {
+            if kinds[&sym] == SymbolKind::Generated {
+                kinds.insert(sym, SymbolKind::Nonterminal);
+            }
+        }
+        kinds.into_iter().collect::<Vec<_>>().into_iter()
+    }
+
+    /// Returns the number of rules for each RHS length that occurs in the grammar.
+    fn rhs_length_histogram<'a>(&'a self) -> BTreeMap<usize, usize> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        let mut histogram = BTreeMap::new();
+        for rule in self.rules() {
+            *histogram.entry(rule.rhs().len()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Returns the nonterminals whose rules are exactly `lhs ::= item | lhs sep item` or
+    /// `lhs ::= item | lhs item`, in any order, sorted by their LHS. These are the rules that the
+    /// rewrite of a left-recursive sequence of one or more items generates.
+    fn left_recursion_groups<'a>(&'a self) -> Vec<LeftRecGroup<Self::Symbol>> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        let mut alternatives = BTreeMap::new();
+        for rule in self.rules() {
+            alternatives.entry(rule.lhs()).or_insert_with(|| vec![]).push(rule.rhs().to_vec());
+        }
+        let mut groups = vec![];
+        for (lhs, mut rhss) in alternatives {
+            if rhss.len() != 2 {
+                continue;
+            }
+            rhss.sort_by_key(|rhs| rhs.len());
+            let (base, recursive) = (&rhss[0][..], &rhss[1][..]);
+            if base.len() != 1 || base[0] == lhs {
+                continue;
+            }
+            let item = base[0];
+            if recursive[0] != lhs || recursive[recursive.len() - 1] != item {
+                continue;
+            }
+            let separator = match recursive.len() {
+                2 => None,
+                3 if recursive[1] != lhs => Some(recursive[1]),
+                _ => continue,
+            };
+            groups.push(LeftRecGroup { lhs: lhs, item: item, separator: separator });
+        }
+        groups
+    }
+
+    /// Returns the indices of rules whose RHS begins with `terminal`.
+    fn rules_starting_with<'a>(&'a self, terminal: Self::Symbol) -> Vec<usize> where
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        self.rules().enumerate().filter(|&(_, rule)| {
+            rule.rhs().first() == Some(&terminal)
+        }).map(|(idx, _)| idx).collect()
+    }
+
+    /// Returns the indices of rules whose RHS has `terminal` in its FIRST set. Unlike with
+    /// `rules_starting_with`, the terminal may follow nullable symbols.
+    fn rules_with_t_in_first<'a>(&'a self, terminal: Self::Symbol) -> Vec<usize> where
+                Self: TerminalSymbolSet,
+                &'a Self: ContextFreeRef<'a, Target=Self> {
+        let first_sets = FirstSets::new(self);
+        self.rules().enumerate().filter(|&(_, rule)| {
+            first_sets.first_set_of(self, rule.rhs()).contains(&Some(terminal))
+        }).map(|(idx, _)| idx).collect()
+    }
+
+    /// Writes the grammar's rules in BNF, grouped by LHS in the order of symbol IDs. Symbols are
+    /// written as `s` followed by their IDs.
+    fn write_bnf<'a, W>(&'a self, w: &mut W) -> fmt::Result where
+                &'a Self: ContextFreeRef<'a, Target=Self>,
+                W: fmt::Write {
+        write_bnf(self, w)
+    }
+
+    /// Writes the grammar's rules as a PEG, grouped by LHS in the order of symbol IDs. Unlike
+    /// alternatives in a context-free grammar, PEG choices are ordered, and they follow the order
+    /// of the grammar's rules. Symbols are written as `s` followed by their IDs.
+    fn write_peg<'a, W>(&'a self, w: &mut W) -> fmt::Result where
+                &'a Self: ContextFreeRef<'a, Target=Self>,
+                W: fmt::Write {
+        write_peg(self, w)
+    }
+
+    /// Writes the grammar as a graph in the DOT language, with edges from rules' LHS to symbols
+    /// on their RHS. Symbols are written as `s` followed by their IDs, in the order of IDs.
+    fn write_dot<'a, W>(&'a self, w: &mut W) -> fmt::Result where
+                &'a Self: ContextFreeRef<'a, Target=Self>,
+                W: fmt::Write {
+        write_dot(self, w)
+    }
+
+    /// Returns a graph with a node for every symbol in the grammar's rules, and edges from the LHS
+    /// of each rule to nonterminals on its RHS. Also returns the nodes of symbols.
+    #[cfg(feature = "petgraph")]
+    fn to_petgraph<'a>(&'a self) -> (Graph<Self::Symbol, ()>, HashMap<Self::Symbol, NodeIndex>)
+            where &'a Self: ContextFreeRef<'a, Target=Self> {
+        to_petgraph(self)
+    }
+
+    /// Returns the LL(1) predict set of every rule for sentences derived from `start`, indexed by
+    /// rule. See `prediction::predict_sets`.
+    fn predict_sets<'a>(&'a self, start: Self::Symbol) -> Vec<BTreeSet<Option<Self::Symbol>>>
+                where Self: TerminalSymbolSet,
+                      &'a Self: ContextFreeRef<'a, Target=Self> {
+        predict_sets(self, start)
+    }
+
+    /// Writes the LL(1) parse table for sentences derived from `start` as a grid of rule
+    /// indices. Fails if the grammar isn't LL(1).
+    fn write_ll1_table<'a, W>(&'a self, start: Self::Symbol, w: &mut W)
+                -> Result<(), Ll1Error<Self::Symbol>> where
+                Self: TerminalSymbolSet,
+                &'a Self: ContextFreeRef<'a, Target=Self>,
+                W: fmt::Write {
+        let table = try!(Ll1Table::new(self, start));
+        try!(table.write(w));
+        Ok(())
+    }
 }
 
 // Traits for working around the lack of higher-order type constructors, more commonly known as HKT
@@ -69,8 +411,22 @@ pub struct Cfg<H = NullHistory, Hs = H, Ss = ConsecutiveSymbols> where Ss: Symbo
     rules: Vec<Rule<H, Ss::Symbol>>,
     /// The array of sequence rules.
     sequence_rules: Vec<Sequence<Hs, Ss::Symbol>>,
+    /// The direction of recursion chosen for each sequence rule, if any.
+    sequence_recursion: Vec<Option<Recursion>>,
+    /// The array of start symbols.
+    start_symbols: Vec<Ss::Symbol>,
+    /// The maximum length of rules' RHS, together with the function that makes histories for
+    /// the rules that hold the split-off parts.
+    max_rhs: Option<(usize, fn(&H) -> H)>,
+    /// The name of the grammar.
+    name: Option<String>,
+    /// Key-value pairs attached to the grammar.
+    metadata: BTreeMap<String, String>,
 }
 
+/// A context-free grammar where terminal symbols are bytes.
+pub type ByteCfg<H = NullHistory, Hs = H> = Cfg<H, Hs, ByteSymbols>;
+
 impl<H, Hs> Cfg<H, Hs> {
     /// Creates an empty context-free grammar.
     pub fn new() -> Cfg<H, Hs> {
@@ -78,13 +434,358 @@ impl<H, Hs> Cfg<H, Hs> {
     }
 }
 
+impl<H, Hs, Ss> Default for Cfg<H, Hs, Ss> where Ss: SymbolSource + Default {
+    fn default() -> Self {
+        Cfg::with_sym_source(Ss::default())
+    }
+}
+
+impl<H, Hs> Cfg<H, Hs, ConsecutiveSymbols> {
+    /// Renumbers the grammar's symbols, so that the symbols in use have consecutive IDs. The start
+    /// symbol of the symbol source keeps its ID. Other symbols keep their relative order. Returns
+    /// the mapping of symbols.
+    pub fn remap(&mut self) -> SymbolMap<NumericSymbol> {
+        let mut used = BTreeSet::new();
+        for rule in &self.rules {
+            used.insert(rule.lhs());
+            used.extend(rule.rhs().iter().cloned());
+        }
+        for seq in &self.sequence_rules {
+            used.insert(seq.lhs);
+            used.insert(seq.rhs);
+            if let Some(sep) = seq.separator.symbol() {
+                used.insert(sep);
+            }
+        }
+        used.extend(self.start_symbols.iter().cloned());
+
+        let (map, sym_source) = SymbolMap::consecutive(used);
+        let new = |sym| map.to_new(sym).unwrap();
+        let rules = mem::replace(&mut self.rules, vec![]);
+        self.rules = rules.into_iter().map(|rule| {
+            let lhs = new(rule.lhs());
+            let rhs = rule.rhs().iter().map(|&sym| new(sym)).collect();
+            Rule::new(lhs, rhs, rule.history)
+        }).collect();
+        for seq in &mut self.sequence_rules {
+            seq.lhs = new(seq.lhs);
+            seq.rhs = new(seq.rhs);
+            seq.separator = seq.separator.map(&new);
+        }
+        for start in &mut self.start_symbols {
+            *start = new(*start);
+        }
+        self.sym_source = sym_source;
+        map
+    }
+
+    /// Returns a copy of the grammar with renumbered symbols, as well as the mapping of symbols.
+    /// The grammar is unchanged. See `remap`.
+    pub fn compacted_clone(&self) -> (Self, SymbolMap<NumericSymbol>) where H: Clone, Hs: Clone {
+        let mut grammar = self.clone();
+        let map = grammar.remap();
+        (grammar, map)
+    }
+}
+
+impl<H, Hs> Cfg<H, Hs, ByteSymbols> {
+    /// Creates an empty context-free grammar over bytes.
+    pub fn over_bytes() -> Self {
+        Cfg::with_sym_source(ByteSymbols::new())
+    }
+
+    /// Adds a rule that derives the given string of bytes.
+    pub fn add_literal(&mut self, lhs: NumericSymbol, bytes: &[u8], history: H) {
+        self.add_rule(lhs, &ByteSymbols::literal(bytes)[..], history);
+    }
+}
+
 impl<H, Hs, Ss> Cfg<H, Hs, Ss> where Ss: SymbolSource {
     /// Creates an empty context-free grammar with the given symbol source.
     pub fn with_sym_source(sym_source: Ss) -> Cfg<H, Hs, Ss> {
+        let start = sym_source.start_sym();
         Cfg {
             sym_source: sym_source,
             rules: vec![],
             sequence_rules: vec![],
+            sequence_recursion: vec![],
+            start_symbols: vec![start],
+            max_rhs: None,
+            name: None,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the name of the grammar, if any.
+    pub fn grammar_name(&self) -> Option<&str> {
+        self.name.as_ref().map(|name| &name[..])
+    }
+
+    /// Names the grammar. The name is written in the header of `write_bnf`.
+    pub fn set_grammar_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    /// Returns the key-value pairs attached to the grammar, in the order of keys.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Attaches a key-value pair to the grammar, and returns the old value of the key, if any.
+    /// Pairs are written in the header of `write_bnf`.
+    pub fn set_metadata(&mut self, key: &str, value: &str) -> Option<String> {
+        self.metadata.insert(key.to_string(), value.to_string())
+    }
+
+    /// Limits the length of RHS of rules added from now on to `max_rhs` symbols, which must be
+    /// at least 2. Longer rules are split into rules with new symbols on their LHS. If `max_rhs`
+    /// is 2, added rules are binarized. The new rules carry no-op histories.
+    pub fn set_max_rhs(&mut self, max_rhs: usize) where H: Action {
+        assert!(max_rhs >= 2);
+        self.max_rhs = Some((max_rhs, Action::no_op as fn(&H) -> H));
+    }
+
+    /// Makes `start` the only start symbol.
+    pub fn set_start(&mut self, start: Ss::Symbol) {
+        self.start_symbols = vec![start];
+    }
+
+    /// Adds a start symbol. Sentences derived from any of the start symbols belong to the
+    /// grammar's language.
+    pub fn add_start(&mut self, start: Ss::Symbol) {
+        if !self.start_symbols.contains(&start) {
+            self.start_symbols.push(start);
+        }
+    }
+
+    /// Replaces all start symbols with a single new one, which has a rule with each of the old
+    /// start symbols on its RHS. Returns the new start symbol.
+    pub fn augment_start(&mut self) -> Ss::Symbol where H: Default {
+        let start = self.next_sym(false);
+        for old_start in mem::replace(&mut self.start_symbols, vec![start]) {
+            // start ::= old_start
+            self.add_rule(start, &[old_start], H::default());
+        }
+        start
+    }
+
+    /// Copies the rules of `other` into this grammar, with new symbols in place of the other
+    /// grammar's symbols, and `placeholder` in place of `other_start`. In effect, `placeholder`
+    /// derives the language of `other_start`. Sequence rules are copied as well. Returns the
+    /// mapping from the other grammar's symbols to this grammar's symbols.
+    pub fn graft<Os>(&mut self,
+                     placeholder: Ss::Symbol,
+                     other: &Cfg<H, Hs, Os>,
+                     other_start: Ss::Symbol) -> SymbolMap<Ss::Symbol> where
+                H: Clone,
+                Hs: Clone,
+                Os: SymbolSource<Symbol=Ss::Symbol> {
+        let mut used = BTreeSet::new();
+        for rule in &other.rules {
+            used.insert(rule.lhs());
+            used.extend(rule.rhs().iter().cloned());
+        }
+        for seq in &other.sequence_rules {
+            used.insert(seq.lhs);
+            used.insert(seq.rhs);
+            used.extend(seq.separator.symbol());
+        }
+        used.remove(&other_start);
+
+        let mut map = SymbolMap::new();
+        map.insert(other_start, placeholder);
+        for sym in used {
+            map.insert(sym, self.next_sym(false));
+        }
+
+        let new = |sym| map.to_new(sym).unwrap();
+        for rule in &other.rules {
+            let rhs = rule.rhs().iter().map(|&sym| new(sym)).collect::<Vec<_>>();
+            self.add_rule(new(rule.lhs()), &rhs[..], rule.history().clone());
+        }
+        for seq in &other.sequence_rules {
+            self.sequence_rules.push(Sequence {
+                lhs: new(seq.lhs),
+                rhs: new(seq.rhs),
+                start: seq.start,
+                end: seq.end,
+                separator: seq.separator.map(&new),
+                history: seq.history.clone(),
+            });
+        }
+        self.sequence_recursion.extend(other.sequence_recursion.iter().cloned());
+        map
+    }
+
+    /// Returns a mutable reference to the grammar's symbol source.
+    pub fn sym_source_mut(&mut self) -> &mut Ss {
+        &mut self.sym_source
+    }
+
+    /// Replaces the history of every rule with a clone of `history`. Sequence rules keep their
+    /// histories.
+    pub fn set_all_histories(&mut self, history: H) where H: Clone {
+        for rule in &mut self.rules {
+            rule.history = history.clone();
+        }
+    }
+
+    /// Creates a grammar that accepts exactly the given sentences, starting from the start
+    /// symbol of `sym_source`. Sentences that share a prefix also share the nonterminals that
+    /// derive the rest of that prefix, so the grammar is shaped like a trie.
+    pub fn from_sentences(sym_source: Ss, sentences: &[Vec<Ss::Symbol>]) -> Cfg<H, Hs, Ss> where
+                H: Default {
+        let mut grammar = Cfg::with_sym_source(sym_source);
+        let start = grammar.start_sym();
+        let mut work_queue = VecDeque::new();
+        work_queue.push_back((start, sentences.iter().map(|s| &s[..]).collect::<Vec<_>>()));
+
+        while let Some((lhs, mut suffixes)) = work_queue.pop_front() {
+            suffixes.sort();
+            suffixes.dedup();
+            // Group suffixes by their first symbol.
+            let mut groups = BTreeMap::new();
+            for suffix in suffixes {
+                if suffix.is_empty() {
+                    // lhs ::= epsilon
+                    grammar.add_rule(lhs, &[], H::default());
+                } else {
+                    groups.entry(suffix[0]).or_insert_with(|| vec![]).push(suffix);
+                }
+            }
+            for (_, group) in groups {
+                // The longest prefix common to the group.
+                let mut prefix_len = group[0].len();
+                for suffix in &group[1..] {
+                    prefix_len = group[0].iter().zip(suffix.iter())
+                                         .take(prefix_len)
+                                         .take_while(|&(a, b)| a == b)
+                                         .count();
+                }
+                let prefix = &group[0][..prefix_len];
+                if group.len() == 1 {
+                    // lhs ::= prefix
+                    grammar.add_rule(lhs, prefix, H::default());
+                } else {
+                    // lhs ::= prefix helper
+                    let helper = grammar.next_sym(false);
+                    let mut rhs = prefix.to_vec();
+                    rhs.push(helper);
+                    grammar.add_rule(lhs, &rhs[..], H::default());
+                    let rest = group.iter().map(|s| &s[prefix_len..]).collect();
+                    work_queue.push_back((helper, rest));
+                }
+            }
+        }
+
+        grammar
+    }
+
+    /// Factors the longest RHS suffix common to alternatives that have the same LHS and end with
+    /// the same symbol. For example, `A ::= b c | d c` becomes `A ::= A' c` and `A' ::= b | d`.
+    /// The factored rule is in the place of the first alternative of its group, and has a no-op
+    /// history. Rules for helpers are added at the end, with the histories of the alternatives.
+    pub fn right_factor(&mut self) where H: Action {
+        let mut rules = mem::replace(&mut self.rules, vec![]).into_iter().map(Some)
+                                                             .collect::<Vec<_>>();
+        let mut groups = vec![];
+        let mut group_indices = HashMap::new();
+        for (idx, rule) in rules.iter().enumerate() {
+            let rule = rule.as_ref().unwrap();
+            if let Some(&last) = rule.rhs.last() {
+                match group_indices.entry((rule.lhs(), last)) {
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(groups.len());
+                        groups.push(vec![idx]);
+                    }
+                    Entry::Occupied(occupied) => {
+                        groups[*occupied.get()].push(idx);
+                    }
+                }
+            }
+        }
+
+        let mut factored = HashMap::new();
+        let mut helper_rules = vec![];
+        for group in groups.into_iter().filter(|group| group.len() >= 2) {
+            // The longest suffix common to the group.
+            let suffix = {
+                let rhs_of = |idx: usize| &rules[idx].as_ref().unwrap().rhs;
+                let first = rhs_of(group[0]);
+                let mut suffix_len = first.len();
+                for &idx in &group[1..] {
+                    suffix_len = first.iter().rev().zip(rhs_of(idx).iter().rev())
+                                      .take(suffix_len)
+                                      .take_while(|&(a, b)| a == b)
+                                      .count();
+                }
+                first[first.len() - suffix_len ..].to_vec()
+            };
+            let helper = self.next_sym(false);
+            let first = rules[group[0]].as_ref().unwrap();
+            // lhs ::= helper suffix
+            let mut rhs = vec![helper];
+            rhs.extend(suffix.iter().cloned());
+            factored.insert(group[0], Rule::new(first.lhs(), rhs, first.history.no_op()));
+            for &idx in &group {
+                let rule = rules[idx].take().unwrap();
+                let prefix_len = rule.rhs.len() - suffix.len();
+                // helper ::= prefix
+                helper_rules.push(Rule::new(helper, rule.rhs[..prefix_len].to_vec(), rule.history));
+            }
+        }
+
+        for (idx, rule) in rules.into_iter().enumerate() {
+            if let Some(rule) = factored.remove(&idx).or(rule) {
+                self.rules.push(rule);
+            }
+        }
+        self.rules.extend(helper_rules);
+    }
+
+    /// Merges rules that have identical LHS and RHS into one rule, in the place of the first of
+    /// them. Their histories are combined with `combine`, in the order in which the rules appear.
+    pub fn merge_by_rhs<F>(&mut self, mut combine: F) where F: FnMut(H, H) -> H {
+        let mut groups = vec![];
+        let mut group_indices = HashMap::new();
+        for rule in mem::replace(&mut self.rules, vec![]) {
+            match group_indices.entry((rule.lhs(), rule.rhs.clone())) {
+                Entry::Vacant(vacant) => {
+                    vacant.insert(groups.len());
+                    groups.push((rule.lhs(), rule.rhs, vec![rule.history]));
+                }
+                Entry::Occupied(occupied) => {
+                    groups[*occupied.get()].2.push(rule.history);
+                }
+            }
+        }
+        for (lhs, rhs, histories) in groups {
+            let mut histories = histories.into_iter();
+            let first = histories.next().unwrap();
+            let history = histories.fold(first, &mut combine);
+            self.rules.push(Rule::new(lhs, rhs, history));
+        }
+    }
+}
+
+impl<H, Hs, Ss> Cfg<Weighted<H>, Hs, Ss> where Ss: SymbolSource {
+    /// Rescales the weights of each nonterminal's rules so that they sum to 1.0, turning them
+    /// into probabilities. If all of a nonterminal's rules have the weight 0.0, they become
+    /// equally probable.
+    pub fn normalize_probabilities(&mut self) {
+        let mut totals = HashMap::new();
+        for rule in &self.rules {
+            let total = totals.entry(rule.lhs()).or_insert((0.0, 0));
+            total.0 += rule.history.weight;
+            total.1 += 1;
+        }
+        for rule in &mut self.rules {
+            let (weight_sum, num_rules) = totals[&rule.lhs()];
+            rule.history.weight = if weight_sum == 0.0 {
+                1.0 / num_rules as f64
+            } else {
+                rule.history.weight / weight_sum
+            };
         }
     }
 }
@@ -95,14 +796,202 @@ impl<H: Action, Hs, Ss> Cfg<H, Hs, Ss>
               Ss: SymbolSource {
     /// Starts building a sequence rule.
     pub fn sequence(&mut self, lhs: Ss::Symbol)
-                -> SequenceRuleBuilder<Hs, &mut Vec<Sequence<Hs, Ss::Symbol>>, Ss::Symbol> {
-        SequenceRuleBuilder::new(&mut self.sequence_rules).sequence(lhs)
+                -> SequenceRuleBuilder<Hs, &mut Self, Ss::Symbol> {
+        SequenceRuleBuilder::new(self).sequence(lhs)
     }
 
     /// Forces a rewrite of sequence rules into grammar rules.
     pub fn rewrite_sequences(&mut self) {
         let sequence_rules = mem::replace(&mut self.sequence_rules, vec![]);
-        SequencesToProductions::rewrite_sequences(&sequence_rules[..], self);
+        let recursion = mem::replace(&mut self.sequence_recursion, vec![]);
+        rewrite_pending(&sequence_rules[..], &recursion[..], self, false);
+    }
+
+    /// Forces a rewrite of sequence rules into grammar rules. Helper symbols are shared between
+    /// sequences, so that repetitions of the same symbol with the same separator don't duplicate
+    /// their rules.
+    pub fn rewrite_sequences_shared(&mut self) {
+        let sequence_rules = mem::replace(&mut self.sequence_rules, vec![]);
+        let recursion = mem::replace(&mut self.sequence_recursion, vec![]);
+        rewrite_pending(&sequence_rules[..], &recursion[..], self, true);
+    }
+
+    /// Removes the upper bounds of sequence rules that are awaiting a rewrite, so that a
+    /// sequence of `start` to `end` repetitions becomes a sequence of at least `start`
+    /// repetitions. Large bounds no longer need many rules, but the upper bounds must be
+    /// enforced after parsing. Returns the LHS and the original bounds of each changed sequence.
+    pub fn relax_bounds(&mut self) -> Vec<(Ss::Symbol, u32, Option<u32>)> {
+        let mut relaxed = vec![];
+        for seq in &mut self.sequence_rules {
+            if seq.end.is_some() {
+                relaxed.push((seq.lhs, seq.start, seq.end));
+                seq.end = None;
+            }
+        }
+        relaxed
+    }
+
+    /// Rewrites a sequence rule that derives the whole language into grammar rules, and makes
+    /// its LHS the only start symbol. Then, the start is augmented with a fresh symbol that
+    /// doesn't occur on any RHS, as LR constructions require. Returns the fresh start symbol.
+    pub fn rewrite_as_start(&mut self, top: Sequence<Hs, Ss::Symbol>) -> Ss::Symbol where
+                H: Default {
+        self.set_start(top.lhs);
+        SequencesToProductions::new(&mut *self).rewrite(top);
+        self.augment_start()
+    }
+
+    /// Writes the grammar in the syntax of Lark. Sequence rules that are awaiting a rewrite are
+    /// written as repetitions. Rules are named in lowercase and terminals in uppercase, with
+    /// names taken from `names`, such as those returned by `bnf::parse_bnf`. Symbols without a
+    /// name are written as `s` followed by their IDs. Characters that Lark doesn't allow in
+    /// names are replaced with `_`, and names that are already taken get a numeric suffix.
+    /// Empty alternatives next to others make them optional instead. See `export::write_lark`.
+    pub fn write_lark<W>(&self, names: &BTreeMap<String, Ss::Symbol>, w: &mut W) -> fmt::Result
+                where W: fmt::Write {
+        let names = names.iter().map(|(name, &sym)| (sym, name.clone())).collect();
+        write_lark(&self.rules, &self.sequence_rules[..], &names, w)
+    }
+
+    /// Returns a grammar for the reverse of the language, with the RHS of each rule reversed.
+    /// Sequence rules are rewritten first. Histories are kept.
+    pub fn reverse(&self) -> Self where Hs: Clone, Ss: Clone {
+        let mut grammar = self.clone();
+        grammar.rewrite_sequences();
+        for rule in &mut grammar.rules {
+            rule.rhs.reverse();
+        }
+        grammar
+    }
+
+    /// Returns a grammar for the nonempty sentences derived from `start`, where no rule has an
+    /// empty RHS, except for `start ::= epsilon` if `start` is nullable. Only rules reachable
+    /// from `start` are kept. Each of them is copied once for every way of leaving out nullable
+    /// symbols on its RHS, with the original history. Sequence rules are rewritten first.
+    pub fn epsilon_free_clone(&self, start: Ss::Symbol) -> Self where Hs: Clone, Ss: Clone {
+        let mut grammar = self.clone();
+        grammar.rewrite_sequences();
+        let nullable = nullable_syms(&grammar);
+        let rules = mem::replace(&mut grammar.rules, vec![]);
+
+        let mut reachable = BTreeSet::new();
+        reachable.insert(start);
+        let mut work = vec![start];
+        while let Some(lhs) = work.pop() {
+            for rule in rules.iter().filter(|rule| rule.lhs() == lhs) {
+                for &sym in rule.rhs() {
+                    if reachable.insert(sym) {
+                        work.push(sym);
+                    }
+                }
+            }
+        }
+
+        let mut start_history = None;
+        for rule in rules.iter().filter(|rule| reachable.contains(&rule.lhs())) {
+            let mut variants = vec![vec![]];
+            for &sym in rule.rhs() {
+                if nullable[sym.usize()] {
+                    let without = variants.clone();
+                    for variant in &mut variants {
+                        variant.push(sym);
+                    }
+                    variants.extend(without);
+                } else {
+                    for variant in &mut variants {
+                        variant.push(sym);
+                    }
+                }
+            }
+            variants.sort();
+            variants.dedup();
+            for variant in variants {
+                if variant.is_empty() {
+                    if rule.lhs() == start && start_history.is_none() {
+                        start_history = Some(rule.history().clone());
+                    }
+                } else {
+                    grammar.add_rule(rule.lhs(), &variant[..], rule.history().clone());
+                }
+            }
+        }
+        if let Some(history) = start_history {
+            grammar.add_rule(start, &[], history);
+        }
+        grammar
+    }
+
+    /// Rewrites a single sequence rule into grammar rules, which are added to the grammar right
+    /// away. Sequence rules that are awaiting a rewrite are left as they are.
+    pub fn add_sequence_rule<R>(&mut self,
+                                lhs: Ss::Symbol,
+                                rhs: Ss::Symbol,
+                                range: R,
+                                separator: Separator<Ss::Symbol>,
+                                history: Hs) where
+                R: RangeArgument<u32> {
+        let sequence = Sequence {
+            lhs: lhs,
+            rhs: rhs,
+            start: range.start().cloned().unwrap_or(0),
+            end: range.end().cloned().map(|end| end - 1),
+            separator: separator,
+            history: history,
+        };
+        SequencesToProductions::new(self).rewrite(sequence);
+    }
+
+    /// Adds rules for a list of `item` separated by `sep`, which is empty only if `at_least_one`
+    /// is false. Returns a new symbol that derives the list.
+    pub fn separated_list(&mut self,
+                          item: Ss::Symbol,
+                          sep: Ss::Symbol,
+                          at_least_one: bool) -> Ss::Symbol where
+                Hs: Default {
+        let top = self.next_sym(false);
+        let start = if at_least_one { 1 } else { 0 };
+        self.add_sequence_rule(top, item, start.., Separator::Proper(sep), Hs::default());
+        top
+    }
+
+    /// Adds rules for `min` to `max` repetitions of `item`, inclusive, separated by `sep`. Returns
+    /// a new symbol that derives them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max`.
+    pub fn bounded_repeat(&mut self,
+                          item: Ss::Symbol,
+                          min: u32,
+                          max: u32,
+                          sep: Separator<Ss::Symbol>) -> Ss::Symbol where
+                Hs: Default {
+        assert!(min <= max, "the minimum number of repetitions is greater than the maximum");
+        let top = self.next_sym(false);
+        let sequence = Sequence {
+            lhs: top,
+            rhs: item,
+            start: min,
+            end: Some(max),
+            separator: sep,
+            history: Hs::default(),
+        };
+        SequencesToProductions::new(self).rewrite(sequence);
+        top
+    }
+
+    /// Adds rules for any number of repetitions of `sym`. Returns a new symbol that derives them.
+    pub fn star(&mut self, sym: Ss::Symbol) -> Ss::Symbol where Hs: Default {
+        let top = self.next_sym(false);
+        self.add_sequence_rule(top, sym, 0.., Separator::Null, Hs::default());
+        top
+    }
+
+    /// Adds rules for one or more repetitions of `sym`. Returns a new symbol that derives them.
+    pub fn plus(&mut self, sym: Ss::Symbol) -> Ss::Symbol where Hs: Default {
+        let top = self.next_sym(false);
+        self.add_sequence_rule(top, sym, 1.., Separator::Null, Hs::default());
+        top
     }
 }
 
@@ -115,14 +1004,32 @@ impl<H: Action, Hs, Ss> ContextFree for Cfg<H, Hs, Ss> where
         &self.sym_source
     }
 
+    fn start_symbols(&self) -> Vec<Ss::Symbol> {
+        self.start_symbols.clone()
+    }
+
     fn binarize<'a>(&'a self) -> BinarizedCfg<Self::History, Self::Source> where
                 &'a Self: ContextFreeRef<'a, Target=Self>,
                 H: Binarize + Clone + 'static,
                 Ss: Clone {
         let mut grammar = BinarizedCfg::from_context_free(self);
-        SequencesToProductions::rewrite_sequences(&self.sequence_rules[..], &mut grammar);
+        let recursion = &self.sequence_recursion[..];
+        rewrite_pending(&self.sequence_rules[..], recursion, &mut grammar, false);
         grammar
     }
+
+    /// Writes the grammar's name and metadata as comments, followed by the rules.
+    fn write_bnf<'a, W>(&'a self, w: &mut W) -> fmt::Result where
+                &'a Self: ContextFreeRef<'a, Target=Self>,
+                W: fmt::Write {
+        if let Some(ref name) = self.name {
+            try!(writeln!(w, "// {}", name));
+        }
+        for (key, value) in &self.metadata {
+            try!(writeln!(w, "// {}: {}", key, value));
+        }
+        write_bnf(self, w)
+    }
 }
 
 impl<'a, H: Action, Hs, Ss> ContextFreeRef<'a> for &'a Cfg<H, Hs, Ss> where
@@ -159,7 +1066,56 @@ impl<H, Hs, Ss> RuleContainer for Cfg<H, Hs, Ss> where
                            rhs: &[Self::Symbol],
                            history: H) {
         self.sym_source.mark_as_nonterminal(lhs);
-        self.rules.push(Rule::new(lhs, rhs.to_vec(), history));
+        let mut rhs = rhs.to_vec();
+        if let Some((max_rhs, no_op)) = self.max_rhs {
+            while rhs.len() > max_rhs {
+                // helper ::= rhs[.. max_rhs]
+                let helper = self.sym_source.next_sym(false);
+                let tail = rhs.split_off(max_rhs);
+                self.rules.push(Rule::new(helper, rhs, no_op(&history)));
+                rhs = Some(helper).into_iter().chain(tail).collect();
+            }
+        }
+        self.rules.push(Rule::new(lhs, rhs, history));
+    }
+}
+
+impl<'a, H, Hs, Ss> SequenceDestination<Hs> for &'a mut Cfg<H, Hs, Ss> where Ss: SymbolSource {
+    type Symbol = Ss::Symbol;
+
+    fn add_sequence(&mut self, seq: Sequence<Hs, Self::Symbol>) {
+        self.sequence_rules.push(seq);
+        self.sequence_recursion.push(None);
+    }
+
+    fn add_sequence_with_recursion(&mut self,
+                                   seq: Sequence<Hs, Self::Symbol>,
+                                   recursion: Recursion) {
+        self.sequence_rules.push(seq);
+        self.sequence_recursion.push(Some(recursion));
+    }
+}
+
+/// Rewrites sequence rules that are awaiting a rewrite, each with its direction of recursion, if
+/// one was chosen.
+fn rewrite_pending<H, S, D>(sequence_rules: &[Sequence<H, S>],
+                            recursion: &[Option<Recursion>],
+                            rules: D,
+                            share_helpers: bool) where
+            D: RuleContainer<History=H::Rewritten, Symbol=S>,
+            H: RewriteSequence,
+            H::Rewritten: Clone,
+            S: GrammarSymbol {
+    let rewriter = SequencesToProductions::new(rules).share_helpers(share_helpers);
+    let mut rewrite = SequenceRuleBuilder::new(rewriter);
+    for (rule, &recursion) in sequence_rules.iter().zip(recursion) {
+        rewrite = rewrite.sequence(rule.lhs)
+                         .separator(rule.separator)
+                         .inclusive(rule.start, rule.end);
+        if let Some(recursion) = recursion {
+            rewrite = rewrite.recursion(recursion);
+        }
+        rewrite = rewrite.rhs_with_history(rule.rhs, &rule.history);
     }
 }
 
@@ -178,8 +1134,26 @@ impl<H, Hs, Ss> SymbolSource for Cfg<H, Hs, Ss> where Ss: SymbolSource {
         self.sym_source.num_syms()
     }
 
+    /// Returns the first of the grammar's start symbols, which may differ from the start
+    /// symbol of the symbol source after `set_start` or `augment_start`.
     fn start_sym(&self) -> Ss::Symbol {
-        self.sym_source.start_sym()
+        self.start_symbols[0]
+    }
+
+    fn has_names(&self) -> bool {
+        self.sym_source.has_names()
+    }
+
+    fn set_name(&mut self, sym: Self::Symbol, name: &str) {
+        self.sym_source.set_name(sym, name)
+    }
+
+    fn name_of(&self, sym: Self::Symbol) -> Option<&str> {
+        self.sym_source.name_of(sym)
+    }
+
+    fn sym_named(&mut self, name: &str) -> Self::Symbol {
+        self.sym_source.sym_named(name)
     }
 }
 
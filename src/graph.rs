@@ -0,0 +1,40 @@
+use std::collections::{BTreeSet, HashMap};
+
+use petgraph::Graph;
+use petgraph::graph::NodeIndex;
+
+use grammar::{ContextFree, ContextFreeRef};
+use rule::GrammarRule;
+
+/// Returns a graph that has a node for every symbol used in the grammar's rules, and an edge from
+/// the LHS of each rule to every nonterminal on its RHS. Nodes are added in the order of symbol
+/// IDs.
+pub fn to_petgraph<'a, G>(grammar: &'a G) -> (Graph<G::Symbol, ()>, HashMap<G::Symbol, NodeIndex>)
+        where G: ContextFree,
+              &'a G: ContextFreeRef<'a, Target=G> {
+    let mut syms = BTreeSet::new();
+    let mut nonterminals = BTreeSet::new();
+    for rule in grammar.rules() {
+        syms.insert(rule.lhs());
+        syms.extend(rule.rhs().iter().cloned());
+        nonterminals.insert(rule.lhs());
+    }
+    let mut edges = BTreeSet::new();
+    for rule in grammar.rules() {
+        for &sym in rule.rhs() {
+            if nonterminals.contains(&sym) {
+                edges.insert((rule.lhs(), sym));
+            }
+        }
+    }
+
+    let mut graph = Graph::new();
+    let mut nodes = HashMap::new();
+    for sym in syms {
+        nodes.insert(sym, graph.add_node(sym));
+    }
+    for (from, to) in edges {
+        graph.add_edge(nodes[&from], nodes[&to], ());
+    }
+    (graph, nodes)
+}
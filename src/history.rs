@@ -2,7 +2,7 @@
 //! more than semantic actions.
 
 use rule::GrammarRule;
-use sequence::Sequence;
+use sequence::{Separator, Sequence};
 use symbol::GrammarSymbol;
 
 /// Used to inform which symbols on a rule's RHS are nullable, and will be eliminated.
@@ -18,12 +18,74 @@ pub enum BinarizedRhsSubset {
 
 /// A history which carries no data. All operations on `NullHistory` are no-op.
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NullHistory;
 
+/// A history together with a weight, such as the rule's probability.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weighted<H> {
+    /// The rule's weight.
+    pub weight: f64,
+    /// The wrapped history.
+    pub history: H,
+}
+
+/// A history that identifies the rule it was attached to, such as by the index of a semantic
+/// action. Rules split by binarization keep the identifier, and record their position.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Traced<H> {
+    /// The identifier of the original rule.
+    pub rule: usize,
+    /// The position of the fragment within the binarized rule, where 0 is the topmost rule.
+    pub fragment: usize,
+    /// The wrapped history.
+    pub history: H,
+}
+
+/// A history that records whether the rule was generated by a rewrite, such as the rules of a
+/// sequence or of its helper symbols. Rules built directly aren't generated.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Generated<H> {
+    /// Whether the rule was generated.
+    pub generated: bool,
+    /// The wrapped history.
+    pub history: H,
+}
+
+/// A part of a sequence rule, from which grammar rules were generated during a rewrite.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SequenceOrigin<S> {
+    /// The LHS of the sequence rule that was rewritten.
+    pub lhs: S,
+    /// The minimum number of repetitions in the part.
+    pub start: u32,
+    /// The inclusive maximum number of repetitions in the part, or `None` if it's unlimited.
+    pub end: Option<u32>,
+    /// The way elements are separated in the part.
+    pub separator: Separator<S>,
+}
+
 /// Trait for history types that may have semantic actions.
-pub trait Action {
+pub trait Action: Sized {
     /// Returns a history with no-op semantic action.
     fn no_op(&self) -> Self;
+
+    /// Checks whether the rule was generated by a rewrite, rather than built directly. Rules
+    /// generated from a sequence rule have histories created by `RewriteSequence::sequence`,
+    /// `sequence_part` or `no_op`, which may record that fact, as `Generated` does.
+    ///
+    /// Returns `false` by default.
+    fn is_generated(&self) -> bool {
+        false
+    }
+
+    /// Returns a history for rules generated from a part of a sequence rule, which is described
+    /// by `origin`. The rewrite calls this instead of `no_op` for helper symbols.
+    ///
+    /// Returns `self.no_op()` by default.
+    fn sequence_part<S>(&self, _origin: &SequenceOrigin<S>) -> Self where S: GrammarSymbol {
+        self.no_op()
+    }
 }
 
 /// Trait for history types that allow the rule to be binarized.
@@ -86,6 +148,54 @@ impl RewriteSequence for NullHistory {
     }
 }
 
+impl<H> Weighted<H> {
+    /// Wraps a history with a weight.
+    pub fn new(weight: f64, history: H) -> Self {
+        Weighted {
+            weight: weight,
+            history: history,
+        }
+    }
+}
+
+/// The weight is 1.0 by default.
+impl<H> Default for Weighted<H> where H: Default {
+    fn default() -> Self {
+        Weighted::new(1.0, H::default())
+    }
+}
+
+/// Rules generated by rewrites have the weight 1.0.
+impl<H> Action for Weighted<H> where H: Action {
+    fn no_op(&self) -> Self {
+        Weighted::new(1.0, self.history.no_op())
+    }
+
+    fn is_generated(&self) -> bool {
+        self.history.is_generated()
+    }
+
+    fn sequence_part<S>(&self, origin: &SequenceOrigin<S>) -> Self where S: GrammarSymbol {
+        Weighted::new(1.0, self.history.sequence_part(origin))
+    }
+}
+
+/// Only the topmost rule of a binarized rule keeps the weight.
+impl<H> Binarize for Weighted<H> where H: Binarize {
+    fn binarize<R>(&self, rule: &R, depth: usize) -> Self where R: GrammarRule {
+        let weight = if depth == 0 { self.weight } else { 1.0 };
+        Weighted::new(weight, self.history.binarize(rule, depth))
+    }
+}
+
+impl<H> RewriteSequence for Weighted<H> where H: RewriteSequence {
+    type Rewritten = Weighted<H::Rewritten>;
+
+    fn sequence<Hs, S>(&self, top: &Sequence<Hs, S>) -> Self::Rewritten where S: GrammarSymbol {
+        Weighted::new(self.weight, self.history.sequence(top))
+    }
+}
+
 impl<'a, T> RewriteSequence for &'a T where T: RewriteSequence {
     type Rewritten = T::Rewritten;
 
@@ -94,3 +204,106 @@ impl<'a, T> RewriteSequence for &'a T where T: RewriteSequence {
         (**self).sequence(top)
     }
 }
+
+impl<H> Traced<H> {
+    /// Wraps a history with the identifier of a rule.
+    pub fn new(rule: usize, history: H) -> Self {
+        Traced {
+            rule: rule,
+            fragment: 0,
+            history: history,
+        }
+    }
+}
+
+/// Generated rules are identified with the rule they were generated for.
+impl<H> Action for Traced<H> where H: Action {
+    fn no_op(&self) -> Self {
+        Traced { history: self.history.no_op(), ..*self }
+    }
+
+    fn is_generated(&self) -> bool {
+        self.history.is_generated()
+    }
+}
+
+impl<H> Binarize for Traced<H> where H: Binarize {
+    fn binarize<R>(&self, rule: &R, depth: usize) -> Self where R: GrammarRule {
+        Traced {
+            rule: self.rule,
+            fragment: depth,
+            history: self.history.binarize(rule, depth),
+        }
+    }
+}
+
+impl<H> EliminateNulling for Traced<H> where H: EliminateNulling {
+    fn eliminate_nulling<R>(&self, rule: &R, which: BinarizedRhsSubset) -> Self where
+            R: GrammarRule {
+        Traced { history: self.history.eliminate_nulling(rule, which), ..*self }
+    }
+}
+
+impl<H> RewriteSequence for Traced<H> where H: RewriteSequence {
+    type Rewritten = Traced<H::Rewritten>;
+
+    fn sequence<Hs, S>(&self, top: &Sequence<Hs, S>) -> Self::Rewritten where S: GrammarSymbol {
+        Traced::new(self.rule, self.history.sequence(top))
+    }
+}
+
+impl<H> Generated<H> {
+    /// Wraps the history of a rule that is built directly.
+    pub fn new(history: H) -> Self {
+        Generated {
+            generated: false,
+            history: history,
+        }
+    }
+
+    fn generated(history: H) -> Self {
+        Generated {
+            generated: true,
+            history: history,
+        }
+    }
+}
+
+impl<H> Action for Generated<H> where H: Action {
+    fn no_op(&self) -> Self {
+        Generated::generated(self.history.no_op())
+    }
+
+    fn is_generated(&self) -> bool {
+        self.generated
+    }
+
+    fn sequence_part<S>(&self, origin: &SequenceOrigin<S>) -> Self where S: GrammarSymbol {
+        Generated::generated(self.history.sequence_part(origin))
+    }
+}
+
+/// Rules split off by binarization are generated.
+impl<H> Binarize for Generated<H> where H: Binarize {
+    fn binarize<R>(&self, rule: &R, depth: usize) -> Self where R: GrammarRule {
+        Generated {
+            generated: self.generated || depth != 0,
+            history: self.history.binarize(rule, depth),
+        }
+    }
+}
+
+impl<H> EliminateNulling for Generated<H> where H: EliminateNulling {
+    fn eliminate_nulling<R>(&self, rule: &R, which: BinarizedRhsSubset) -> Self where
+            R: GrammarRule {
+        Generated { history: self.history.eliminate_nulling(rule, which), ..*self }
+    }
+}
+
+impl<H> RewriteSequence for Generated<H> where H: RewriteSequence {
+    type Rewritten = Generated<H::Rewritten>;
+
+    fn sequence<Hs, S>(&self, top: &Sequence<Hs, S>) -> Self::Rewritten where S: GrammarSymbol {
+        Generated::generated(self.history.sequence(top))
+    }
+}
@@ -13,24 +13,39 @@ extern crate bit_matrix;
 extern crate bit_vec;
 extern crate collections;
 extern crate core;
+#[cfg(feature = "petgraph")]
+extern crate petgraph;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 
 mod binarized;
+pub mod bnf;
 pub mod cycles;
+pub mod derivation;
+pub mod dfa;
+mod export;
+mod finite;
 mod grammar;
+#[cfg(feature = "petgraph")]
+mod graph;
 pub mod history;
+mod mandatory;
+mod nullable;
 pub mod precedence;
 pub mod prediction;
+pub mod recognizer;
 mod rhs_closure;
 mod rule;
 pub mod rule_builder;
 pub mod rule_container;
 pub mod sequence;
 pub mod sequence_builder;
-mod sequence_destination;
+pub mod sequence_destination;
 pub mod symbol;
 pub mod usefulness;
 
 pub use binarized::BinarizedCfg;
-pub use grammar::{Cfg, ContextFree, ContextFreeRef, ContextFreeMut};
-pub use rule::GrammarRule;
+pub use grammar::{ByteCfg, Cfg, ContextFree, ContextFreeRef, ContextFreeMut};
+pub use rule::{GrammarRule, Rule, RuleRef};
 pub use symbol::SymbolSource;
@@ -0,0 +1,73 @@
+use bit_vec::BitVec;
+
+use grammar::{ContextFree, ContextFreeRef};
+use rule::GrammarRule;
+use symbol::{SymbolSource, GrammarSymbol, SymbolBitSet};
+
+/// Returns the set of terminals that occur in every sentence derived from `start`. The set is
+/// empty if `start` derives no sentences. Symbols that have no rules are treated as terminals.
+pub fn mandatory_terminals<'a, G>(grammar: &'a G, start: G::Symbol) -> SymbolBitSet<G::Symbol>
+        where G: ContextFree,
+              &'a G: ContextFreeRef<'a, Target=G> {
+    let num_syms = grammar.sym_source().num_syms();
+    let mut nonterminal = BitVec::from_elem(num_syms, false);
+    for rule in grammar.rules() {
+        nonterminal.set(rule.lhs().usize(), true);
+    }
+
+    // The terminals that every sentence of a symbol contains, or `None` for the set of all
+    // terminals. Sets only shrink, starting from `None` for nonterminals, until they reach the
+    // greatest fixed point. Symbols that derive no sentences keep `None`.
+    let mut mandatory: Vec<Option<BitVec>> = (0 .. num_syms).map(|sym| {
+        if nonterminal[sym] {
+            None
+        } else {
+            let mut own = BitVec::from_elem(num_syms, false);
+            own.set(sym, true);
+            Some(own)
+        }
+    }).collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in grammar.rules() {
+            let lhs = rule.lhs().usize();
+            // The union of the sets on the RHS.
+            let mut rhs_set = Some(BitVec::from_elem(num_syms, false));
+            for sym in rule.rhs() {
+                match mandatory[sym.usize()] {
+                    Some(ref set) => {
+                        if let Some(ref mut union) = rhs_set {
+                            union.union(set);
+                        }
+                    }
+                    None => {
+                        rhs_set = None;
+                        break;
+                    }
+                }
+            }
+            // Intersected with the LHS's set.
+            let rhs_set = match rhs_set {
+                Some(set) => set,
+                None => continue,
+            };
+            let new_set = match mandatory[lhs] {
+                Some(ref set) => {
+                    let mut intersection = set.clone();
+                    intersection.intersect(&rhs_set);
+                    intersection
+                }
+                None => rhs_set,
+            };
+            if mandatory[lhs].as_ref() != Some(&new_set) {
+                mandatory[lhs] = Some(new_set);
+                changed = true;
+            }
+        }
+    }
+    let bits = mandatory.swap_remove(start.usize()).unwrap_or_else(|| {
+        BitVec::from_elem(num_syms, false)
+    });
+    SymbolBitSet::from_bit_vec(bits)
+}
@@ -0,0 +1,50 @@
+use bit_matrix::FixedBitMatrix;
+use bit_vec::BitVec;
+
+use grammar::{ContextFree, ContextFreeRef};
+use rhs_closure::RhsClosure;
+use rule::GrammarRule;
+use symbol::{SymbolSource, GrammarSymbol};
+
+/// Returns the set of nullable symbols, which derive the empty string.
+pub fn nullable_syms<'a, G>(grammar: &'a G) -> BitVec where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G> {
+    let num_syms = grammar.sym_source().num_syms();
+    let mut nullable = BitVec::from_elem(num_syms, false);
+
+    for rule in grammar.rules() {
+        if rule.rhs().is_empty() {
+            nullable.set(rule.lhs().usize(), true);
+        }
+    }
+
+    RhsClosure::new(grammar).rhs_closure(&mut nullable);
+    nullable
+}
+
+/// Returns the nonterminals that are nullable and left-recursive, in the order of their IDs.
+/// Left recursion may be indirect, and may pass through nullable symbols at the beginning of
+/// a rule's RHS.
+pub fn nullable_left_recursive_syms<'a, G>(grammar: &'a G) -> Vec<G::Symbol> where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G> {
+    let num_syms = grammar.sym_source().num_syms();
+    let nullable = nullable_syms(grammar);
+    let mut left_corner = FixedBitMatrix::new(num_syms, num_syms);
+
+    for rule in grammar.rules() {
+        for &sym in rule.rhs() {
+            left_corner.set(rule.lhs().usize(), sym.usize(), true);
+            if !nullable[sym.usize()] {
+                break;
+            }
+        }
+    }
+
+    left_corner.transitive_closure();
+
+    (0 .. num_syms).filter(|&id| nullable[id] && left_corner[(id, id)])
+                   .map(|id| G::Symbol::from(id as u64))
+                   .collect()
+}
@@ -1,6 +1,10 @@
 //! Prediction for predictive parsers.
 
+use std::cmp;
 use std::collections::{BTreeMap, BTreeSet};
+use std::collections::btree_map::Entry;
+use std::fmt;
+use std::mem;
 
 use grammar::{ContextFree, ContextFreeRef};
 use rule::GrammarRule;
@@ -20,6 +24,26 @@ pub struct FollowSets<S> where S: GrammarSymbol {
     map: PerSymbolSets<S>,
 }
 
+/// An LL(1) parse table.
+pub struct Ll1Table<S> where S: GrammarSymbol {
+    /// Mapping from nonterminals and lookahead terminals to indices of rules. The end of input
+    /// is represented by `None`.
+    map: BTreeMap<(S, Option<S>), usize>,
+    nonterminals: BTreeSet<S>,
+    terminals: BTreeSet<S>,
+}
+
+/// An error in the construction or rendering of an LL(1) parse table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Ll1Error<S> {
+    /// The grammar isn't LL(1). Holds a nonterminal, a lookahead terminal or `None` for the end
+    /// of input, and the indices of two rules for that nonterminal which are both predicted by
+    /// the lookahead.
+    Conflict(S, Option<S>, usize, usize),
+    /// The table couldn't be written.
+    Fmt(fmt::Error),
+}
+
 // Based on code by Niko Matsakis.
 impl<S> FirstSets<S> where S: GrammarSymbol {
     /// Compute all FIRST sets of the grammar.
@@ -53,11 +77,71 @@ impl<S> FirstSets<S> where S: GrammarSymbol {
         this
     }
 
+    /// Computes the FIRST set of a single symbol. Only rules for nonterminals that `sym` derives
+    /// take part in the computation. The result is equal to the symbol's entry in the FIRST
+    /// sets of the whole grammar.
+    pub fn first_of<'a, G>(grammar: &'a G, sym: S) -> BTreeSet<Option<S>> where
+                G: ContextFree<Symbol=S> + TerminalSymbolSet,
+                &'a G: ContextFreeRef<'a, Target=G> {
+        if grammar.is_terminal(sym) {
+            return Some(Some(sym)).into_iter().collect();
+        }
+
+        let mut rules_by_lhs = BTreeMap::new();
+        for rule in grammar.rules() {
+            rules_by_lhs.entry(rule.lhs()).or_insert_with(|| vec![]).push(rule.rhs().to_vec());
+        }
+        let mut relevant = vec![];
+        let mut work_stack = vec![sym];
+        let mut seen = BTreeSet::new();
+        seen.insert(sym);
+        while let Some(lhs) = work_stack.pop() {
+            if let Some(rhss) = rules_by_lhs.get(&lhs) {
+                for rhs in rhss {
+                    relevant.push((lhs, &rhs[..]));
+                    for &rhs_sym in rhs {
+                        if !grammar.is_terminal(rhs_sym) && seen.insert(rhs_sym) {
+                            work_stack.push(rhs_sym);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut this = FirstSets {
+            map: BTreeMap::new(),
+        };
+        let mut lookahead = vec![];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(lhs, rhs) in &relevant {
+                this.first_set_collect(grammar, &mut lookahead, rhs);
+                let first_set = this.map.entry(lhs).or_insert_with(|| BTreeSet::new());
+                let prev_cardinality = first_set.len();
+                first_set.extend(lookahead.iter().cloned());
+                lookahead.clear();
+                changed |= first_set.len() != prev_cardinality;
+            }
+        }
+
+        this.map.remove(&sym).unwrap_or_else(|| BTreeSet::new())
+    }
+
     /// Returns a reference to FIRST sets.
     pub fn first_sets(&self) -> &PerSymbolSets<S> {
         &self.map
     }
 
+    /// Returns the FIRST set of a string of symbols. It contains `None` if the string is
+    /// nullable.
+    pub fn first_set_of<G>(&self, grammar: &G, rhs: &[S]) -> BTreeSet<Option<S>> where
+                G: ContextFree<Symbol=S> + TerminalSymbolSet {
+        let mut lookahead = vec![];
+        self.first_set_collect(grammar, &mut lookahead, rhs);
+        lookahead.into_iter().collect()
+    }
+
     /// Compute a FIRST set.
     fn first_set_collect<G>(&self, grammar: &G, vec: &mut Vec<Option<S>>, rhs: &[S]) where
                 G: ContextFree<Symbol=S> + TerminalSymbolSet {
@@ -93,19 +177,166 @@ impl<S> FirstSets<S> where S: GrammarSymbol {
     }
 }
 
+/// FIRST sets that are kept up to date as rules are added to the grammar. A nonterminal is
+/// nullable if its FIRST set contains `None`.
+pub struct IncrementalFirstSets<G> where G: ContextFree {
+    grammar: G,
+    first_sets: FirstSets<G::Symbol>,
+    rules: Vec<(G::Symbol, Vec<G::Symbol>)>,
+    /// Indices of rules that have each symbol on their RHS, including symbols that were
+    /// terminals when the rule was added.
+    occurrences: BTreeMap<G::Symbol, Vec<usize>>,
+}
+
+/// Symbols whose sets changed after a rule was added, in the order of IDs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnalysisDelta<S> {
+    /// Symbols that became nullable.
+    pub nullable: Vec<S>,
+    /// Symbols whose FIRST sets changed, including symbols that became nullable. Sets grow,
+    /// unless a terminal that occurs on some RHS gets a rule, and stops being a terminal.
+    pub first: Vec<S>,
+}
+
+impl<G> IncrementalFirstSets<G> where G: ContextFree + TerminalSymbolSet {
+    /// Takes the grammar and computes FIRST sets of its rules.
+    pub fn new(grammar: G) -> Self where for<'a> &'a G: ContextFreeRef<'a, Target=G> {
+        let rules: Vec<_> = grammar.rules().map(|rule| {
+            (rule.lhs(), rule.rhs().to_vec())
+        }).collect();
+        let mut this = IncrementalFirstSets {
+            grammar: grammar,
+            first_sets: FirstSets { map: BTreeMap::new() },
+            rules: vec![],
+            occurrences: BTreeMap::new(),
+        };
+        for (lhs, rhs) in rules {
+            this.relax(lhs, rhs);
+        }
+        this
+    }
+
+    /// Adds a rule to the grammar. FIRST sets are updated only for its LHS and for the
+    /// nonterminals that derive it. However, if the LHS was a terminal that occurs on the RHS of
+    /// other rules, all sets are recomputed. Returns the symbols whose sets changed.
+    pub fn add_rule_incremental(&mut self,
+                                lhs: G::Symbol,
+                                rhs: &[G::Symbol],
+                                history: G::History) -> AnalysisDelta<G::Symbol> {
+        let was_terminal = self.grammar.is_terminal(lhs);
+        self.grammar.add_rule(lhs, rhs, history);
+        if was_terminal && !self.grammar.is_terminal(lhs) && self.occurrences.contains_key(&lhs) {
+            self.recompute(lhs, rhs.to_vec())
+        } else {
+            self.relax(lhs, rhs.to_vec())
+        }
+    }
+
+    /// Computes all sets from scratch, with a new rule. Returns the symbols whose sets changed.
+    fn recompute(&mut self, lhs: G::Symbol, rhs: Vec<G::Symbol>) -> AnalysisDelta<G::Symbol> {
+        let old = mem::replace(&mut self.first_sets.map, BTreeMap::new());
+        let mut rules = mem::replace(&mut self.rules, vec![]);
+        rules.push((lhs, rhs));
+        self.occurrences.clear();
+        for (lhs, rhs) in rules {
+            self.relax(lhs, rhs);
+        }
+        let mut delta = AnalysisDelta { nullable: vec![], first: vec![] };
+        for (&sym, set) in &self.first_sets.map {
+            let old_set = old.get(&sym);
+            if old_set != Some(set) {
+                delta.first.push(sym);
+            }
+            if set.contains(&None) && !old_set.map_or(false, |set| set.contains(&None)) {
+                delta.nullable.push(sym);
+            }
+        }
+        delta
+    }
+
+    fn relax(&mut self, lhs: G::Symbol, rhs: Vec<G::Symbol>) -> AnalysisDelta<G::Symbol> {
+        let idx = self.rules.len();
+        let mut referenced = BTreeSet::new();
+        for &sym in &rhs {
+            if referenced.insert(sym) {
+                self.occurrences.entry(sym).or_insert_with(|| vec![]).push(idx);
+            }
+        }
+        self.rules.push((lhs, rhs));
+        self.first_sets.map.entry(lhs).or_insert_with(|| BTreeSet::new());
+
+        let mut nullable = BTreeSet::new();
+        let mut first = BTreeSet::new();
+        let mut work_stack = vec![idx];
+        let mut lookahead = vec![];
+        while let Some(idx) = work_stack.pop() {
+            let (lhs, ref rhs) = self.rules[idx];
+            self.first_sets.first_set_collect(&self.grammar, &mut lookahead, rhs);
+            let first_set = self.first_sets.map.get_mut(&lhs).unwrap();
+            let mut grew = false;
+            for terminal in lookahead.drain(..) {
+                if first_set.insert(terminal) {
+                    grew = true;
+                    if terminal.is_none() {
+                        nullable.insert(lhs);
+                    }
+                }
+            }
+            if grew {
+                first.insert(lhs);
+                if let Some(occurrences) = self.occurrences.get(&lhs) {
+                    work_stack.extend(occurrences.iter().cloned());
+                }
+            }
+        }
+        AnalysisDelta {
+            nullable: nullable.into_iter().collect(),
+            first: first.into_iter().collect(),
+        }
+    }
+
+    /// Returns a reference to FIRST sets.
+    pub fn first_sets(&self) -> &PerSymbolSets<G::Symbol> {
+        &self.first_sets.map
+    }
+
+    /// Checks whether the symbol is nullable.
+    pub fn is_nullable(&self, sym: G::Symbol) -> bool {
+        self.first_sets.map.get(&sym).map_or(false, |set| set.contains(&None))
+    }
+
+    /// Returns a reference to the grammar.
+    pub fn grammar(&self) -> &G {
+        &self.grammar
+    }
+
+    /// Returns the grammar.
+    pub fn into_grammar(self) -> G {
+        self.grammar
+    }
+}
+
 impl<S> FollowSets<S> where S: GrammarSymbol {
     /// Compute all FOLLOW sets of the grammar.
     /// Returns FollowSets.
     pub fn new<'a, G>(grammar: &'a G, first_sets: &FirstSets<S>) -> Self
             where G: ContextFree<Symbol=S> + TerminalSymbolSet,
                   &'a G: ContextFreeRef<'a, Target=G> {
+        FollowSets::with_start(grammar, grammar.start_sym(), first_sets)
+    }
+
+    /// Compute all FOLLOW sets of the grammar, where the end of input may follow `start`.
+    /// Returns FollowSets.
+    pub fn with_start<'a, G>(grammar: &'a G, start: S, first_sets: &FirstSets<S>) -> Self
+            where G: ContextFree<Symbol=S> + TerminalSymbolSet,
+                  &'a G: ContextFreeRef<'a, Target=G> {
         let mut this = FollowSets {
             map: BTreeMap::new()
         };
 
         for rule in grammar.rules() {
             let follow_set = this.map.entry(rule.lhs()).or_insert_with(|| BTreeSet::new());
-            if rule.lhs() == grammar.start_sym() {
+            if rule.lhs() == start {
                 follow_set.insert(None);
             }
         }
@@ -144,3 +375,107 @@ impl<S> FollowSets<S> where S: GrammarSymbol {
         &self.map
     }
 }
+
+/// Returns the predict set of every rule for recognizing sentences derived from `start`, indexed
+/// by the rule's position in the sequence of the grammar's rules. The predict set is the FIRST set
+/// of the RHS, together with the FOLLOW set of the LHS if the RHS is nullable. The end of input
+/// is represented by `None`.
+pub fn predict_sets<'a, G>(grammar: &'a G, start: G::Symbol) -> Vec<BTreeSet<Option<G::Symbol>>>
+        where G: ContextFree + TerminalSymbolSet,
+              &'a G: ContextFreeRef<'a, Target=G> {
+    let first_sets = FirstSets::new(grammar);
+    let follow_sets = FollowSets::with_start(grammar, start, &first_sets);
+    grammar.rules().map(|rule| {
+        let mut lookahead = first_sets.first_set_of(grammar, rule.rhs());
+        if lookahead.remove(&None) {
+            lookahead.extend(follow_sets.map[&rule.lhs()].iter().cloned());
+        }
+        lookahead
+    }).collect()
+}
+
+impl<S> Ll1Table<S> where S: GrammarSymbol {
+    /// Builds the LL(1) parse table for recognizing sentences derived from `start`. Rules are
+    /// identified by their position in the sequence of the grammar's rules.
+    pub fn new<'a, G>(grammar: &'a G, start: S) -> Result<Self, Ll1Error<S>>
+            where G: ContextFree<Symbol=S> + TerminalSymbolSet,
+                  &'a G: ContextFreeRef<'a, Target=G> {
+        let predict_sets = predict_sets(grammar, start);
+        let mut table = Ll1Table {
+            map: BTreeMap::new(),
+            nonterminals: BTreeSet::new(),
+            terminals: BTreeSet::new(),
+        };
+
+        for ((idx, rule), lookahead) in grammar.rules().enumerate().zip(predict_sets) {
+            table.nonterminals.insert(rule.lhs());
+            table.terminals.extend(rule.rhs().iter().cloned().filter(|&sym| {
+                grammar.is_terminal(sym)
+            }));
+
+            for terminal in lookahead {
+                match table.map.entry((rule.lhs(), terminal)) {
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(idx);
+                    }
+                    Entry::Occupied(occupied) => {
+                        let other = *occupied.get();
+                        if other != idx {
+                            return Err(Ll1Error::Conflict(rule.lhs(), terminal, other, idx));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Returns the index of the rule predicted for `nonterminal` by `lookahead`, which is `None`
+    /// at the end of input.
+    pub fn get(&self, nonterminal: S, lookahead: Option<S>) -> Option<usize> {
+        self.map.get(&(nonterminal, lookahead)).cloned()
+    }
+
+    /// Writes the table as a grid with a row for every nonterminal and a column for every
+    /// terminal, followed by `$` for the end of input. Symbols are shown as their IDs. Cells hold
+    /// indices of rules, or `-` if no rule is predicted.
+    pub fn write<W>(&self, w: &mut W) -> fmt::Result where W: fmt::Write {
+        let columns = self.terminals.iter().map(|&sym| Some(sym))
+                                    .chain(Some(None).into_iter())
+                                    .collect::<Vec<_>>();
+        let cell = |nonterminal: S, lookahead: Option<S>| {
+            self.get(nonterminal, lookahead).map_or("-".to_string(), |idx| idx.to_string())
+        };
+        let label = |sym: Option<S>| sym.map_or("$".to_string(), |sym| sym.usize().to_string());
+
+        let mut width = 1;
+        for &nonterminal in &self.nonterminals {
+            width = cmp::max(width, label(Some(nonterminal)).len());
+            for &lookahead in &columns {
+                width = cmp::max(width, cmp::max(label(lookahead).len(),
+                                                 cell(nonterminal, lookahead).len()));
+            }
+        }
+
+        try!(write!(w, "{:>1$}", "", width));
+        for &lookahead in &columns {
+            try!(write!(w, " {:>1$}", label(lookahead), width));
+        }
+        try!(writeln!(w, ""));
+        for &nonterminal in &self.nonterminals {
+            try!(write!(w, "{:>1$}", label(Some(nonterminal)), width));
+            for &lookahead in &columns {
+                try!(write!(w, " {:>1$}", cell(nonterminal, lookahead), width));
+            }
+            try!(writeln!(w, ""));
+        }
+        Ok(())
+    }
+}
+
+impl<S> From<fmt::Error> for Ll1Error<S> {
+    fn from(error: fmt::Error) -> Self {
+        Ll1Error::Fmt(error)
+    }
+}
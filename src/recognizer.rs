@@ -0,0 +1,368 @@
+//! Recognition of sentences with the Earley algorithm.
+
+use std::collections::HashSet;
+
+use bit_vec::BitVec;
+
+use grammar::{ContextFree, ContextFreeRef};
+use nullable::nullable_syms;
+use rule::GrammarRule;
+use symbol::{SymbolBitSet, SymbolSource, GrammarSymbol, TerminalTable};
+
+/// An Earley recognizer. Symbols that aren't on the LHS of any rule are terminals.
+pub struct Recognizer<S> where S: GrammarSymbol {
+    /// Rules in the order in which the grammar yields them.
+    rules: Vec<(S, Vec<S>)>,
+    /// Indices of rules, grouped by their LHS.
+    rules_by_lhs: Vec<Vec<usize>>,
+    /// The set of nullable symbols.
+    nullable: BitVec,
+}
+
+/// A rule with a dot in its RHS, together with the position where the rule's recognition began.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Item {
+    rule: usize,
+    dot: usize,
+    origin: usize,
+}
+
+/// The set of items at one position in the input.
+struct EarleySet {
+    items: Vec<Item>,
+    contains: HashSet<Item>,
+}
+
+/// State for reconstructing a leftmost derivation from the chart.
+struct Derivation<'a, S> where S: GrammarSymbol + 'a {
+    recognizer: &'a Recognizer<S>,
+    chart: &'a [EarleySet],
+    input: &'a [S],
+    /// Symbols derived on the path to the tree's current node. Prevents infinite recursion
+    /// through cyclic derivations.
+    path: Vec<(S, usize, usize)>,
+}
+
+impl<S> Recognizer<S> where S: GrammarSymbol {
+    /// Prepares the grammar for recognition.
+    pub fn new<'a, G>(grammar: &'a G) -> Self where
+                G: ContextFree<Symbol=S>,
+                &'a G: ContextFreeRef<'a, Target=G> {
+        let num_syms = grammar.sym_source().num_syms();
+        let mut rules = vec![];
+        let mut rules_by_lhs = vec![vec![]; num_syms];
+
+        for (idx, rule) in grammar.rules().enumerate() {
+            rules_by_lhs[rule.lhs().usize()].push(idx);
+            rules.push((rule.lhs(), rule.rhs().to_vec()));
+        }
+
+        Recognizer {
+            rules: rules,
+            rules_by_lhs: rules_by_lhs,
+            nullable: nullable_syms(grammar),
+        }
+    }
+
+    /// Checks whether `start` derives `input`.
+    pub fn recognize(&self, start: S, input: &[S]) -> bool {
+        let chart = self.chart(start, input);
+        self.rules_by_lhs(start).iter().any(|&rule| {
+            chart[input.len()].contains.contains(&self.completed(rule, 0))
+        })
+    }
+
+    /// Checks whether `start` derives a sentence whose terminals match `input`, one by one.
+    /// Terminals are matched with `matches(terminal, &input[pos])`.
+    pub fn recognize_with<I, F>(&self, start: S, input: &[I], matches: F) -> bool where
+                F: Fn(S, &I) -> bool {
+        let chart = self.chart_with(start, input.len(), |sym, pos| matches(sym, &input[pos]));
+        self.rules_by_lhs(start).iter().any(|&rule| {
+            chart[input.len()].contains.contains(&self.completed(rule, 0))
+        })
+    }
+
+    /// Checks whether `start` derives a sentence whose terminals match `input`, one by one.
+    /// Terminals are matched with their matchers from the table. Terminals without a matcher
+    /// don't match any input.
+    pub fn recognize_with_table<I, T>(&self, start: S, input: &[I], table: &TerminalTable<S, T>)
+                -> bool where
+                T: Fn(&I) -> bool {
+        self.recognize_with(start, input, |sym, value| {
+            table.terminal_value(sym).map_or(false, |matcher| matcher(value))
+        })
+    }
+
+    /// Returns the rules applied in a leftmost derivation of `input` from `start`, or `None`
+    /// if `start` doesn't derive `input`. Rules are identified by their position in the
+    /// sequence of the grammar's rules.
+    pub fn leftmost_derivation(&self, start: S, input: &[S]) -> Option<Vec<usize>> {
+        let chart = self.chart(start, input);
+        let mut derivation = Derivation {
+            recognizer: self,
+            chart: &chart[..],
+            input: input,
+            path: vec![],
+        };
+        let mut applied_rules = vec![];
+        if derivation.derive(start, 0, input.len(), &mut applied_rules) {
+            Some(applied_rules)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the multiset of rules that take part in a derivation of `input` from `start`, as
+    /// rule indices in ascending order, or `None` if `start` doesn't derive `input`. Rules that
+    /// the derivation applies more than once are repeated.
+    pub fn explain(&self, start: S, input: &[S]) -> Option<Vec<usize>> {
+        self.leftmost_derivation(start, input).map(|mut rules| {
+            rules.sort();
+            rules
+        })
+    }
+
+    /// Returns the set of terminals that may follow `prefix` in sentences derived from `start`.
+    /// The set is empty if no sentence begins with `prefix`.
+    pub fn valid_next(&self, start: S, prefix: &[S]) -> SymbolBitSet<S> {
+        let chart = self.chart(start, prefix);
+        let mut valid = SymbolBitSet::new(self.rules_by_lhs.len());
+        for item in &chart[prefix.len()].items {
+            if let Some(&sym) = self.rules[item.rule].1.get(item.dot) {
+                if self.is_terminal(sym) {
+                    valid.insert(sym);
+                }
+            }
+        }
+        valid
+    }
+
+    /// Builds the Earley chart, which has one set of items for every position in the input.
+    fn chart(&self, start: S, input: &[S]) -> Vec<EarleySet> {
+        self.chart_with(start, input.len(), |sym, pos| input[pos] == sym)
+    }
+
+    /// Builds the Earley chart for an input of length `len`. The terminal `sym` is scanned at
+    /// `pos` if `scan(sym, pos)` is true.
+    fn chart_with<F>(&self, start: S, len: usize, scan: F) -> Vec<EarleySet> where
+                F: Fn(S, usize) -> bool {
+        let mut chart = (0 .. len + 1).map(|_| EarleySet::new()).collect::<Vec<_>>();
+        for &rule in self.rules_by_lhs(start) {
+            chart[0].insert(Item { rule: rule, dot: 0, origin: 0 });
+        }
+
+        for pos in 0 .. len + 1 {
+            let mut idx = 0;
+            while idx < chart[pos].items.len() {
+                let item = chart[pos].items[idx];
+                idx += 1;
+                let (lhs, ref rhs) = self.rules[item.rule];
+                if let Some(&sym) = rhs.get(item.dot) {
+                    let next = Item { dot: item.dot + 1, ..item };
+                    if self.is_terminal(sym) {
+                        // Scan.
+                        if pos < len && scan(sym, pos) {
+                            chart[pos + 1].insert(next);
+                        }
+                    } else {
+                        // Predict.
+                        for &rule in self.rules_by_lhs(sym) {
+                            chart[pos].insert(Item { rule: rule, dot: 0, origin: pos });
+                        }
+                        // Nullable symbols are completed immediately, as per Aycock and Horspool.
+                        if self.nullable[sym.usize()] {
+                            chart[pos].insert(next);
+                        }
+                    }
+                } else {
+                    // Complete.
+                    let mut waiting = vec![];
+                    for &parent in &chart[item.origin].items {
+                        if self.rules[parent.rule].1.get(parent.dot) == Some(&lhs) {
+                            waiting.push(Item { dot: parent.dot + 1, ..parent });
+                        }
+                    }
+                    for parent in waiting {
+                        chart[pos].insert(parent);
+                    }
+                }
+            }
+        }
+
+        chart
+    }
+
+    fn rules_by_lhs(&self, sym: S) -> &[usize] {
+        self.rules_by_lhs.get(sym.usize()).map(|rules| &rules[..]).unwrap_or(&[])
+    }
+
+    fn is_terminal(&self, sym: S) -> bool {
+        self.rules_by_lhs(sym).is_empty()
+    }
+
+    fn completed(&self, rule: usize, origin: usize) -> Item {
+        Item { rule: rule, dot: self.rules[rule].1.len(), origin: origin }
+    }
+}
+
+impl EarleySet {
+    fn new() -> Self {
+        EarleySet {
+            items: vec![],
+            contains: HashSet::new(),
+        }
+    }
+
+    fn insert(&mut self, item: Item) {
+        if self.contains.insert(item) {
+            self.items.push(item);
+        }
+    }
+}
+
+impl<'a, S> Derivation<'a, S> where S: GrammarSymbol {
+    /// Appends the rules of a leftmost derivation of `input[from .. to]` from `sym`.
+    fn derive(&mut self, sym: S, from: usize, to: usize, applied_rules: &mut Vec<usize>) -> bool {
+        if self.path.contains(&(sym, from, to)) {
+            return false;
+        }
+        self.path.push((sym, from, to));
+        let mut found = false;
+        for &rule in self.recognizer.rules_by_lhs(sym) {
+            let completed = self.recognizer.completed(rule, from);
+            if self.chart[to].contains.contains(&completed) {
+                applied_rules.push(rule);
+                let dot = completed.dot;
+                if self.derive_rhs(rule, dot, from, to, &mut vec![], applied_rules) {
+                    found = true;
+                    break;
+                }
+                applied_rules.pop();
+            }
+        }
+        self.path.pop();
+        found
+    }
+
+    /// Splits `input[from .. to]` among the first `dot` symbols of the rule's RHS, from right
+    /// to left. Once the split is complete, appends derivations of the nonterminals in
+    /// `children`.
+    fn derive_rhs(&mut self,
+                  rule: usize,
+                  dot: usize,
+                  from: usize,
+                  to: usize,
+                  children: &mut Vec<(S, usize, usize)>,
+                  applied_rules: &mut Vec<usize>) -> bool {
+        if dot == 0 {
+            if from != to {
+                return false;
+            }
+            let len = applied_rules.len();
+            for &(sym, child_from, child_to) in children.iter().rev() {
+                if !self.derive(sym, child_from, child_to, applied_rules) {
+                    applied_rules.truncate(len);
+                    return false;
+                }
+            }
+            return true;
+        }
+
+        let sym = self.recognizer.rules[rule].1[dot - 1];
+        let prev = Item { rule: rule, dot: dot - 1, origin: from };
+        if self.recognizer.is_terminal(sym) {
+            to > from && self.input[to - 1] == sym && self.chart[to - 1].contains.contains(&prev)
+                && self.derive_rhs(rule, dot - 1, from, to - 1, children, applied_rules)
+        } else {
+            for mid in (from .. to + 1).rev() {
+                if self.chart[mid].contains.contains(&prev) && self.completes(sym, mid, to) {
+                    children.push((sym, mid, to));
+                    let found = self.derive_rhs(rule, dot - 1, from, mid, children, applied_rules);
+                    children.pop();
+                    if found {
+                        return true;
+                    }
+                }
+            }
+            false
+        }
+    }
+
+    /// Checks whether `sym` was recognized at `input[from .. to]`.
+    fn completes(&self, sym: S, from: usize, to: usize) -> bool {
+        self.recognizer.rules_by_lhs(sym).iter().any(|&rule| {
+            self.chart[to].contains.contains(&self.recognizer.completed(rule, from))
+        })
+    }
+}
+
+/// Checks whether `start` derives `input` with the CYK algorithm. The grammar must be in Chomsky
+/// normal form: every rule has either two nonterminals or a single terminal on its RHS, except
+/// that `start` may derive the empty string. Symbols that aren't on the LHS of any rule are
+/// terminals.
+pub fn cyk_recognize<'a, G>(grammar: &'a G, start: G::Symbol, input: &[G::Symbol]) -> bool where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G> {
+    let num_syms = grammar.sym_source().num_syms();
+    let mut nonterminal = BitVec::from_elem(num_syms, false);
+    for rule in grammar.rules() {
+        nonterminal.set(rule.lhs().usize(), true);
+    }
+    let mut terminal_rules = vec![];
+    let mut binary_rules = vec![];
+    let mut accepts_empty = false;
+    for rule in grammar.rules() {
+        let lhs = rule.lhs().usize();
+        match rule.rhs().len() {
+            0 => {
+                debug_assert!(rule.lhs() == start, "only the start symbol may be nullable");
+                accepts_empty = true;
+            }
+            1 => {
+                debug_assert!(!nonterminal[rule.rhs()[0].usize()], "unit rules aren't in CNF");
+                terminal_rules.push((lhs, rule.rhs()[0]));
+            }
+            2 => {
+                let (left, right) = (rule.rhs()[0].usize(), rule.rhs()[1].usize());
+                debug_assert!(nonterminal[left] && nonterminal[right],
+                              "binary rules must have two nonterminals");
+                binary_rules.push((lhs, left, right));
+            }
+            _ => debug_assert!(false, "rules must have at most two symbols on the RHS"),
+        }
+    }
+    if input.is_empty() {
+        return accepts_empty;
+    }
+
+    // table[len - 1][pos] holds the nonterminals that derive `input[pos .. pos + len]`.
+    let n = input.len();
+    let mut table = vec![];
+    table.push(input.iter().map(|&sym| {
+        let mut derives = BitVec::from_elem(num_syms, false);
+        for &(lhs, terminal) in &terminal_rules {
+            if terminal == sym {
+                derives.set(lhs, true);
+            }
+        }
+        derives
+    }).collect::<Vec<_>>());
+    for len in 2 .. n + 1 {
+        let mut row = vec![];
+        for pos in 0 .. n - len + 1 {
+            let mut derives = BitVec::from_elem(num_syms, false);
+            for split in 1 .. len {
+                let prefix = &table[split - 1][pos];
+                let suffix = &table[len - split - 1][pos + split];
+                for &(lhs, left, right) in &binary_rules {
+                    if prefix[left] && suffix[right] {
+                        derives.set(lhs, true);
+                    }
+                }
+            }
+            row.push(derives);
+        }
+        table.push(row);
+    }
+    table[n - 1][0][start.usize()]
+}
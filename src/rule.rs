@@ -13,6 +13,21 @@ pub trait GrammarRule {
     fn rhs(&self) -> &[Self::Symbol];
     /// Returns a reference to the history carried with the rule.
     fn history(&self) -> &Self::History;
+
+    /// Returns the length of the rule's right-hand side.
+    fn rhs_len(&self) -> usize {
+        self.rhs().len()
+    }
+
+    /// Checks whether the rule's right-hand side is empty.
+    fn is_nullable_rule(&self) -> bool {
+        self.rhs().is_empty()
+    }
+
+    /// Checks whether the rule's right-hand side has exactly one symbol.
+    fn is_unary(&self) -> bool {
+        self.rhs().len() == 1
+    }
 }
 
 impl<'a, R> GrammarRule for &'a R where R: GrammarRule {
@@ -26,9 +41,12 @@ impl<'a, R> GrammarRule for &'a R where R: GrammarRule {
 
 /// Typical grammar rule representation.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Rule<H, S> where S: GrammarSymbol {
     lhs: S,
+    /// The rule's right-hand side.
     pub rhs: Vec<S>,
+    /// The history carried with the rule.
     pub history: H,
 }
 
@@ -50,6 +68,7 @@ impl<H, S> GrammarRule for Rule<H, S> where S: GrammarSymbol {
 }
 
 impl<H, S> Rule<H, S> where S: GrammarSymbol {
+    /// Creates a rule.
     pub fn new(lhs: S, rhs: Vec<S>, history: H) -> Self {
         Rule {
             lhs: lhs,
@@ -57,15 +76,40 @@ impl<H, S> Rule<H, S> where S: GrammarSymbol {
             history: history,
         }
     }
+
+    /// Formats the rule as `lhs ::= rhs`, with `ε` in place of an empty RHS. Symbols are
+    /// formatted with `name`.
+    pub fn pretty<F>(&self, name: F) -> String where F: Fn(S) -> String {
+        let mut string = format!("{} ::=", name(self.lhs));
+        if self.rhs.is_empty() {
+            string.push_str(" ε");
+        }
+        for &sym in &self.rhs {
+            string.push(' ');
+            string.push_str(&name(sym));
+        }
+        string
+    }
 }
 
 /// References rule's components.
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct RuleRef<'a, H, S> where S: GrammarSymbol + 'a, H: 'a {
+    /// The rule's left-hand side.
     pub lhs: S,
+    /// The rule's right-hand side.
     pub rhs: &'a [S],
+    /// The history carried with the rule.
     pub history: &'a H,
 }
 
+impl<'a, H, S> RuleRef<'a, H, S> where S: GrammarSymbol {
+    /// Creates an owned rule with copies of the components.
+    pub fn into_owned(self) -> Rule<H, S> where H: Clone {
+        Rule::new(self.lhs, self.rhs.to_vec(), self.history.clone())
+    }
+}
+
 // Can't derive because of the type parameter.
 impl<'a, H, S> Copy for RuleRef<'a, H, S> where S: GrammarSymbol {}
 
@@ -1,6 +1,12 @@
 //! Abstraction for collections of rules.
 
-use symbol::SymbolSource;
+use std::fmt;
+use std::mem;
+use std::slice;
+
+use export::write_rules_dot;
+use rule::{GrammarRule, Rule, RuleRef};
+use symbol::{ConsecutiveSymbols, NumericSymbol, SymbolMap, SymbolSource};
 
 /// Trait for rule and symbol containers.
 pub trait RuleContainer: SymbolSource {
@@ -16,6 +22,23 @@ pub trait RuleContainer: SymbolSource {
     fn add_rule(&mut self, lhs: Self::Symbol,
                            rhs: &[Self::Symbol],
                            history: Self::History);
+    /// Retains only the rules specified by the predicate, which inspects each rule through a
+    /// borrowed `RuleRef`.
+    fn retain_rules<F>(&mut self, mut f: F) where
+            F: FnMut(RuleRef<Self::History, Self::Symbol>) -> bool {
+        self.retain(|lhs, rhs, history| {
+            f(RuleRef {
+                lhs: lhs,
+                rhs: rhs,
+                history: history,
+            })
+        });
+    }
+    /// Removes all rules for which `f(&history)` returns true.
+    fn remove_rules_where_history<F>(&mut self, mut f: F) where
+            F: FnMut(&Self::History) -> bool {
+        self.retain(|_, _, history| !f(history));
+    }
 }
 
 impl<'a, D> RuleContainer for &'a mut D where D: RuleContainer {
@@ -26,9 +49,159 @@ impl<'a, D> RuleContainer for &'a mut D where D: RuleContainer {
         (**self).retain(f);
     }
 
+    fn retain_rules<F>(&mut self, f: F) where
+                F: FnMut(RuleRef<Self::History, Self::Symbol>) -> bool {
+        (**self).retain_rules(f);
+    }
+
     fn add_rule(&mut self, lhs: Self::Symbol,
                            rhs: &[Self::Symbol],
                            history: Self::History) {
         (**self).add_rule(lhs, rhs, history);
     }
 }
+
+/// A plain list of rules, together with a symbol source. Useful for collecting rules, such as
+/// those generated by `SequencesToProductions`.
+#[derive(Clone, Debug)]
+pub struct RuleVec<H, Ss = ConsecutiveSymbols> where Ss: SymbolSource {
+    sym_source: Ss,
+    rules: Vec<Rule<H, Ss::Symbol>>,
+}
+
+impl<H> RuleVec<H> {
+    /// Creates an empty list of rules with a new source of numeric symbols.
+    pub fn new() -> Self {
+        RuleVec::with_sym_source(ConsecutiveSymbols::new())
+    }
+}
+
+impl<H, Ss> RuleVec<H, Ss> where Ss: SymbolSource {
+    /// Creates an empty list of rules with the given symbol source.
+    pub fn with_sym_source(sym_source: Ss) -> Self {
+        RuleVec {
+            sym_source: sym_source,
+            rules: vec![],
+        }
+    }
+
+    /// Returns an iterator over the rules, in the order of insertion.
+    pub fn iter(&self) -> slice::Iter<Rule<H, Ss::Symbol>> {
+        self.rules.iter()
+    }
+
+    /// Returns the rules.
+    pub fn rules(&self) -> &[Rule<H, Ss::Symbol>] {
+        &self.rules
+    }
+
+    /// Replaces the history of every rule with a clone of `history`.
+    pub fn set_all_histories(&mut self, history: H) where H: Clone {
+        for rule in &mut self.rules {
+            rule.history = history.clone();
+        }
+    }
+
+    /// Writes the rules as a graph in the DOT language, with edges from rules' LHS to symbols on
+    /// their RHS. Nodes are labeled with `label(sym)`. Symbols without a label, such as helpers
+    /// allocated by rewrites, are drawn dashed.
+    pub fn write_dot<F, W>(&self, label: F, w: &mut W) -> fmt::Result where
+                F: Fn(Ss::Symbol) -> Option<String>,
+                W: fmt::Write {
+        write_rules_dot(&self.rules, label, w)
+    }
+
+    /// Returns the rules, consuming the list.
+    pub fn into_rules(self) -> Vec<Rule<H, Ss::Symbol>> {
+        self.rules
+    }
+}
+
+impl<H> RuleVec<H, ConsecutiveSymbols> {
+    /// Renumbers the symbols, so that the symbols in use have consecutive IDs. The start symbol
+    /// keeps its ID. Other symbols keep their relative order, and rules keep their order and
+    /// histories. Returns the mapping of symbols.
+    pub fn remap(&mut self) -> SymbolMap<NumericSymbol> {
+        let used = self.rules.iter().flat_map(|rule| {
+            Some(rule.lhs()).into_iter().chain(rule.rhs().iter().cloned())
+        }).collect::<Vec<_>>();
+        let (map, sym_source) = SymbolMap::consecutive(used);
+        let new = |sym| map.to_new(sym).unwrap();
+        let rules = mem::replace(&mut self.rules, vec![]);
+        self.rules = rules.into_iter().map(|rule| {
+            let lhs = new(rule.lhs());
+            let rhs = rule.rhs().iter().map(|&sym| new(sym)).collect();
+            Rule::new(lhs, rhs, rule.history)
+        }).collect();
+        self.sym_source = sym_source;
+        map
+    }
+}
+
+impl<H, Ss> Default for RuleVec<H, Ss> where Ss: SymbolSource + Default {
+    fn default() -> Self {
+        RuleVec::with_sym_source(Ss::default())
+    }
+}
+
+impl<H, Ss> SymbolSource for RuleVec<H, Ss> where Ss: SymbolSource {
+    type Symbol = Ss::Symbol;
+
+    fn next_sym(&mut self, terminal: bool) -> Self::Symbol {
+        self.sym_source.next_sym(terminal)
+    }
+
+    fn mark_as_nonterminal(&mut self, sym: Self::Symbol) {
+        self.sym_source.mark_as_nonterminal(sym)
+    }
+
+    fn num_syms(&self) -> usize {
+        self.sym_source.num_syms()
+    }
+
+    fn start_sym(&self) -> Ss::Symbol {
+        self.sym_source.start_sym()
+    }
+
+    fn has_names(&self) -> bool {
+        self.sym_source.has_names()
+    }
+
+    fn set_name(&mut self, sym: Self::Symbol, name: &str) {
+        self.sym_source.set_name(sym, name)
+    }
+
+    fn name_of(&self, sym: Self::Symbol) -> Option<&str> {
+        self.sym_source.name_of(sym)
+    }
+
+    fn sym_named(&mut self, name: &str) -> Self::Symbol {
+        self.sym_source.sym_named(name)
+    }
+}
+
+impl<H, Ss> RuleContainer for RuleVec<H, Ss> where Ss: SymbolSource {
+    type History = H;
+
+    fn retain<F>(&mut self, mut f: F) where
+                F: FnMut(Self::Symbol, &[Self::Symbol], &Self::History) -> bool {
+        self.rules.retain(|rule| f(rule.lhs(), rule.rhs(), rule.history()));
+    }
+
+    fn retain_rules<F>(&mut self, mut f: F) where
+                F: FnMut(RuleRef<Self::History, Self::Symbol>) -> bool {
+        self.rules.retain(|rule| {
+            f(RuleRef {
+                lhs: rule.lhs(),
+                rhs: &rule.rhs,
+                history: &rule.history,
+            })
+        });
+    }
+
+    fn add_rule(&mut self, lhs: Self::Symbol,
+                           rhs: &[Self::Symbol],
+                           history: Self::History) {
+        self.rules.push(Rule::new(lhs, rhs.to_vec(), history));
+    }
+}
@@ -4,6 +4,7 @@ use self::Separator::*;
 
 /// Sequence rule representation.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Sequence<H, S> {
     /// The rule's left-hand side.
     pub lhs: S,
@@ -22,6 +23,7 @@ pub struct Sequence<H, S> {
 
 /// The separator symbol and mode of separation in a sequence, or `Null` for no separation.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Separator<S> {
     /// Separation with the trailing separator included. In other words, all elements are followed
     /// by the separator.
@@ -35,6 +37,67 @@ pub enum Separator<S> {
     Null,
 }
 
+/// A nonterminal whose rules have the shape of a left-recursive sequence of one or more items,
+/// `lhs ::= item | lhs sep item`, as generated by the rewrite of such a sequence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LeftRecGroup<S> {
+    /// The nonterminal.
+    pub lhs: S,
+    /// The repeated symbol.
+    pub item: S,
+    /// The separator, or `None` for `lhs ::= item | lhs item`.
+    pub separator: Option<S>,
+}
+
+/// The direction of recursion in rules for sequences with unlimited repetitions.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Recursion {
+    /// Rules of the form `seq ::= seq item`.
+    Left,
+    /// Rules of the form `seq ::= item seq`.
+    Right,
+}
+
+impl<H, S> Sequence<H, S> where S: Copy {
+    /// Returns the rule's left-hand side.
+    pub fn lhs(&self) -> S {
+        self.lhs
+    }
+
+    /// Returns the rule's right-hand side, which is the repeated symbol.
+    pub fn rhs(&self) -> S {
+        self.rhs
+    }
+
+    /// Returns the minimum number of repetitions.
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Returns the inclusive maximum number of repetitions, or `None` if it's unlimited.
+    pub fn end(&self) -> Option<u32> {
+        self.end
+    }
+
+    /// Returns the separator.
+    pub fn separator(&self) -> Separator<S> {
+        self.separator
+    }
+
+    /// Returns the history carried with the sequence rule.
+    pub fn history(&self) -> &H {
+        &self.history
+    }
+
+    /// Formats the sequence rule as `lhs ::= rhs{start,end}`, followed by the separator as in
+    /// `Separator::pretty`. Symbols are formatted with `name`.
+    pub fn pretty<F>(&self, name: F) -> String where F: Fn(S) -> String {
+        let end = self.end.map_or(String::new(), |end| end.to_string());
+        format!("{} ::= {}{{{},{}}}{}",
+                name(self.lhs), name(self.rhs), self.start, end, self.separator.pretty(&name))
+    }
+}
 
 impl<H, S> Sequence<H, S> {
     /// Assigns the inclusive range of the number of repetitions.
@@ -44,15 +107,85 @@ impl<H, S> Sequence<H, S> {
         self
     }
 
-    /// Assigns the separator symbol and mode of separation.
-    pub fn separator(mut self, sep: Separator<S>) -> Self {
+    /// Assigns the range of at most `n` repetitions, including zero.
+    pub fn at_most(self, n: u32) -> Self {
+        self.inclusive(0, Some(n))
+    }
+
+    /// Assigns the unlimited range of at least `n` repetitions.
+    pub fn at_least(self, n: u32) -> Self {
+        self.inclusive(n, None)
+    }
+
+    /// Assigns the range of exactly `n` repetitions.
+    pub fn repeat(self, n: u32) -> Self {
+        self.inclusive(n, Some(n))
+    }
+
+    /// Assigns the separator symbol and mode of separation. Named apart from the `separator`
+    /// getter.
+    pub fn with_separator(mut self, sep: Separator<S>) -> Self {
         self.separator = sep;
         self
     }
 }
 
+/// Checks whether two sequence rules derive the same language for any item. Their LHS and histories
+/// are ignored. The check is conservative: it compares bounds and
+/// separation after normalization, so sequences that are equivalent only for some items, such
+/// as nullable ones, are reported as different.
+pub fn sequences_equivalent<H, S>(a: &Sequence<H, S>, b: &Sequence<H, S>) -> bool where
+            S: Copy + Eq {
+    // Both derive only the empty string.
+    if a.end == Some(0) || b.end == Some(0) {
+        return a.end == b.end;
+    }
+    // A proper separator never occurs between fewer than two elements.
+    let normalized = |seq: &Sequence<H, S>| {
+        match seq.separator {
+            Proper(_) if seq.end == Some(1) => Null,
+            separator => separator,
+        }
+    };
+    a.rhs == b.rhs && a.start == b.start && a.end == b.end && normalized(a) == normalized(b)
+}
+
 impl<S> Separator<S> {
+    /// Returns the separator symbol, or `None` for no separation.
+    pub fn symbol(self) -> Option<S> {
+        match self {
+            Trailing(sep) | Proper(sep) | Liberal(sep) => Some(sep),
+            Null => None,
+        }
+    }
+
+    /// Replaces the separator symbol with `f(sep)`.
+    pub fn map<T, F>(self, f: F) -> Separator<T> where F: FnOnce(S) -> T {
+        match self {
+            Trailing(sep) => Trailing(f(sep)),
+            Proper(sep) => Proper(f(sep)),
+            Liberal(sep) => Liberal(f(sep)),
+            Null => Null,
+        }
+    }
+
+    /// Formats the separator as ` % sep` for proper, ` %% sep` for trailing and ` %? sep` for
+    /// liberal separation, or as an empty string for no separation. The symbol is formatted with
+    /// `name`.
+    pub fn pretty<F>(self, name: F) -> String where F: Fn(S) -> String {
+        match self {
+            Proper(sep) => format!(" % {}", name(sep)),
+            Trailing(sep) => format!(" %% {}", name(sep)),
+            Liberal(sep) => format!(" %? {}", name(sep)),
+            Null => String::new(),
+        }
+    }
+
     /// Returns the kind of separation for a prefix sequence.
+    ///
+    /// A separated sequence may be split into a prefix and a non-empty suffix. Every element of
+    /// the prefix is then followed by a separator, so `Proper` and `Liberal` separation become
+    /// `Trailing`. Other kinds of separation are unchanged.
     pub fn prefix_separator(self) -> Self {
         match self {
             Proper(sep) | Liberal(sep) => Trailing(sep),
@@ -3,15 +3,17 @@
 use collections::range::RangeArgument;
 
 use history::RewriteSequence;
-use sequence::{Separator, Sequence};
+use rule_container::RuleContainer;
+use sequence::{Recursion, Separator, Sequence};
 use sequence_destination::SequenceDestination;
-use symbol::GrammarSymbol;
+use symbol::{GrammarSymbol, SymbolSource};
 
 /// Sequence rule builder.
 pub struct SequenceRuleBuilder<H, D, S> where S: GrammarSymbol {
     lhs: Option<S>,
     range: Option<(u32, Option<u32>)>,
     separator: Separator<S>,
+    recursion: Option<Recursion>,
     history: Option<H>,
     destination: D,
 }
@@ -27,6 +29,7 @@ impl<H, D, S> SequenceRuleBuilder<H, D, S> where
             range: None,
             history: None,
             separator: Separator::Null,
+            recursion: None,
             destination: destination,
         }
     }
@@ -48,6 +51,13 @@ impl<H, D, S> SequenceRuleBuilder<H, D, S> where
         self.separator(Separator::Proper(sym))
     }
 
+    /// Assigns the direction of recursion, which is used on the next call to `rhs` or
+    /// `rhs_with_history`. Otherwise, the destination decides.
+    pub fn recursion(mut self, recursion: Recursion) -> Self {
+        self.recursion = Some(recursion);
+        self
+    }
+
     /// Assigns the rule history, which is used on the next call to `rhs`, or overwritten by a call
     /// to `rhs_with_history`.
     pub fn history(mut self, history: H) -> Self {
@@ -61,26 +71,92 @@ impl<H, D, S> SequenceRuleBuilder<H, D, S> where
         self
     }
 
+    /// Assigns the range of `start` up to `end` repetitions, not including `end`. Equivalent to
+    /// `inclusive(start, Some(end - 1))`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end` is zero.
+    pub fn exclusive(self, start: u32, end: u32) -> Self {
+        assert!(end != 0, "the exclusive end of a sequence range must be positive");
+        self.inclusive(start, Some(end - 1))
+    }
+
+    /// Assigns the range of the number of repetitions, such as `1..` for one or more. The end
+    /// of the range is exclusive, so `..m` means at most `m - 1` repetitions.
+    pub fn range<T>(self, range: T) -> Self where T: RangeArgument<u32> {
+        let start = range.start().cloned().unwrap_or(0);
+        match range.end() {
+            Some(&end) => self.exclusive(start, end),
+            None => self.inclusive(start, None),
+        }
+    }
+
+    /// Assigns the range of at most `n` repetitions, including zero.
+    pub fn at_most(self, n: u32) -> Self {
+        self.inclusive(0, Some(n))
+    }
+
+    /// Assigns the unlimited range of at least `n` repetitions.
+    pub fn at_least(self, n: u32) -> Self {
+        self.inclusive(n, None)
+    }
+
     /// Adds a sequence rule to the grammar.
     pub fn rhs<T>(mut self, rhs: S, range: T) -> Self where
                 T: RangeArgument<u32>,
                 H: Default {
         let history = self.history.take();
-        self.inclusive(range.start().cloned().unwrap_or(0), range.end().cloned().map(|end| end - 1))
-            .rhs_with_history(rhs, history.unwrap_or_else(|| H::default()))
+        self.range(range).rhs_with_history(rhs, history.unwrap_or_else(|| H::default()))
+    }
+
+    /// Adds a sequence rule to the grammar, with a new helper symbol as the item. The helper
+    /// derives each of `alternatives`, so `at_least(1).item_alt(&[&[x], &[y]])` stands for
+    /// `(x | y)+`. The helper's rules carry the default history. If the destination has names,
+    /// the helper is named after its alternatives, such as `(x | y)`.
+    pub fn item_alt(mut self, alternatives: &[&[S]]) -> Self where
+                H: Default,
+                D: RuleContainer<Symbol=S>,
+                <D as RuleContainer>::History: Default {
+        let item = self.destination.next_sym(false);
+        if self.destination.has_names() {
+            let name = alternation_name(&self.destination, alternatives);
+            self.destination.set_name(item, &name);
+        }
+        for alternative in alternatives {
+            self.destination.add_rule(item, alternative, Default::default());
+        }
+        let history = self.history.take();
+        self.rhs_with_history(item, history.unwrap_or_else(|| H::default()))
     }
 
     /// Adds a sequence rule to the grammar.
     pub fn rhs_with_history(mut self, rhs: S, history: H) -> Self {
         let (start, end) = self.range.take().unwrap();
-        self.destination.add_sequence(Sequence {
+        let sequence = Sequence {
             lhs: self.lhs.unwrap(),
             rhs: rhs,
             start: start,
             end: end,
             separator: self.separator,
             history: history,
-        });
+        };
+        match self.recursion.take() {
+            Some(recursion) => self.destination.add_sequence_with_recursion(sequence, recursion),
+            None => self.destination.add_sequence(sequence),
+        }
         self
     }
 }
+
+/// Makes up a name for a helper symbol that derives alternatives, such as `(x | y z)`.
+fn alternation_name<Ss>(sym_source: &Ss, alternatives: &[&[Ss::Symbol]]) -> String where
+            Ss: SymbolSource {
+    let name_of = |sym: Ss::Symbol| {
+        sym_source.name_of(sym).map_or_else(|| format!("s{}", sym.usize()), |name| name.to_string())
+    };
+    let alternatives = alternatives.iter().map(|alternative| {
+        alternative.iter().map(|&sym| name_of(sym)).collect::<Vec<_>>().join(" ")
+    }).collect::<Vec<_>>();
+    format!("({})", alternatives.join(" | "))
+}
@@ -1,11 +1,17 @@
+//! Sequence rules can be stored in containers, or rewritten into grammar rules.
+
 use collections::range::RangeArgument;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::u32;
 use std::collections::hash_map::Entry;
+use std::marker::PhantomData;
+use std::mem;
 
-use history::{Action, RewriteSequence};
+use history::{Action, RewriteSequence, SequenceOrigin};
+use rule::Rule;
 use rule_builder::RuleBuilder;
-use rule_container::RuleContainer;
-use sequence::{Separator, Sequence};
+use rule_container::{RuleContainer, RuleVec};
+use sequence::{Recursion, Separator, Sequence};
 use sequence::Separator::{Trailing, Proper, Liberal};
 use sequence_builder::SequenceRuleBuilder;
 use symbol::{GrammarSymbol, SymbolSource};
@@ -16,25 +22,194 @@ pub trait SequenceDestination<H> {
     type Symbol;
     /// Inserts a sequence rule.
     fn add_sequence(&mut self, seq: Sequence<H, Self::Symbol>);
+
+    /// Inserts a sequence rule, which is rewritten with the given direction of recursion. By
+    /// default, the direction is left to the destination.
+    fn add_sequence_with_recursion(&mut self, seq: Sequence<H, Self::Symbol>, _: Recursion) {
+        self.add_sequence(seq);
+    }
+}
+
+/// A destination that passes sequence rules to a closure.
+pub struct FnDestination<F, S> {
+    callback: F,
+    marker: PhantomData<S>,
 }
 
-pub struct SequencesToProductions<H, D> where
+/// Rewrites sequence rules into grammar rules, which are inserted into a rule container. The
+/// lifetime `'c` bounds the callback registered with `on_new_symbol`.
+pub struct SequencesToProductions<'c, H, D> where
             H: RewriteSequence,
             D: RuleContainer {
     destination: D,
     stack: Vec<Sequence<H::Rewritten, D::Symbol>>,
-    map: HashMap<PartialSequence<D::Symbol>, D::Symbol>,
+    /// The LHS of the sequence rule that is being rewritten.
+    top_lhs: Option<D::Symbol>,
+    map: HashMap<HelperKey<D::Symbol>, D::Symbol>,
+    /// Keys of map entries that were added during the current rewrite.
+    allocated: Vec<HelperKey<D::Symbol>>,
+    /// Symbols allocated by `separator_string`, and the strings they derive.
+    separator_strings: HashMap<D::Symbol, Vec<D::Symbol>>,
+    /// The symbol allocated by `separator_string` for each string.
+    separator_syms: HashMap<Vec<D::Symbol>, D::Symbol>,
+    /// Symbols allocated by `separator_string` whose rules haven't been generated yet.
+    pending_separators: HashSet<D::Symbol>,
+    /// Symbols whose rules were generated by `separator_string` during the current rewrite.
+    emitted_separators: Vec<D::Symbol>,
+    /// The LHS of rules that refer to each helper symbol.
+    parents: HashMap<D::Symbol, HashSet<D::Symbol>>,
+    treat_max_as_unbounded: bool,
+    right_linear: bool,
+    /// The maximum number of rules generated by `try_rewrite` for one sequence.
+    max_rules: Option<usize>,
+    /// The maximum number of RHS symbols in rules generated by `try_rewrite` for one sequence.
+    max_symbols: Option<usize>,
+    /// The counts of emitted rules and symbols when the current `try_rewrite` began, or `None`
+    /// outside of `try_rewrite`.
+    limits_from: Option<(usize, usize)>,
+    /// The limit that the current `try_rewrite` exceeded in the middle of a step.
+    exceeded: Option<SequenceErrorKind>,
+    share_helpers: bool,
+    emit_grouped: bool,
+    recursion: Recursion,
+    expansion: ExpansionStrategy,
+    /// Symbols that are known to be nullable.
+    nullable: HashSet<D::Symbol>,
+    nullable_separator: NullableSeparator,
+    /// Rules that are waiting to be emitted in groups.
+    buffer: Vec<(D::Symbol, Vec<D::Symbol>, D::History)>,
+    on_new_symbol: Option<Box<FnMut(D::Symbol, &PartialSequence<D::Symbol>) + 'c>>,
+    stats: ReduceStats,
+    rewrite_stats: RewriteStats,
+    liberal_parts: HashMap<D::Symbol, LiberalParts<D::Symbol>>,
+}
+
+/// An iterator over the grammar rules of a sequence rule's rewrite. See
+/// `SequencesToProductions::rewrite_iter`.
+pub struct RewriteIter<'a, 'c: 'a, H, D> where
+            H: RewriteSequence + 'a,
+            D: RuleContainer + 'a {
+    rewriter: &'a mut SequencesToProductions<'c, H, D>,
+}
+
+/// An error in the rewrite of a sequence rule.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SequenceError<S> {
+    /// The LHS of the sequence rule.
+    pub lhs: S,
+    /// The minimum number of repetitions.
+    pub start: u32,
+    /// The inclusive maximum number of repetitions, or `None` if it's unlimited.
+    pub end: Option<u32>,
+    /// The kind of the error.
+    pub kind: SequenceErrorKind,
+}
+
+/// The kinds of errors in rewrites of sequence rules.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SequenceErrorKind {
+    /// The minimum number of repetitions is greater than the maximum.
+    InvalidBounds,
+    /// The rewrite would generate more rules than allowed. Holds the limit.
+    TooManyRules(usize),
+    /// The rewrite would generate rules with more symbols in total than allowed. Holds the
+    /// limit.
+    TooManySymbols(usize),
+    /// The separator is nullable, and `NullableSeparator::Reject` is in effect.
+    NullableSeparator,
+}
+
+/// The treatment of separators that are known to be nullable. With such a separator, items may
+/// end up adjacent, and a liberal sequence is ambiguous, since a trailing separator that derives
+/// the empty string can't be told apart from a missing one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NullableSeparator {
+    /// Sequences are rewritten as usual.
+    Allow,
+    /// Sequences are rejected. `try_rewrite` fails, and `rewrite` panics.
+    Reject,
+    /// Liberal separation is rewritten as trailing separation, which derives the same strings
+    /// without the ambiguity.
+    Disambiguate,
+}
+
+/// The way in which sequences with a bounded number of elements are rewritten.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExpansionStrategy {
+    /// Sequences are split in halves, with a helper symbol for each part. Rules are short, and
+    /// the number of rules grows logarithmically with the number of elements.
+    Balanced,
+    /// Each length gets a single rule that spells out all elements and separators, such as
+    /// `seq ::= item sep item | item sep item sep item`. No helper symbols are needed for
+    /// bounded sequences, but rules grow with the number of elements.
+    Linear,
 }
 
-// A key into a private map.
+/// The symbols that derive the two parts of a sequence with liberal separation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LiberalParts<S> {
+    /// Derives the sequence with proper separation.
+    pub proper: S,
+    /// Derives the sequence with trailing separation.
+    pub trailing: S,
+}
+
+/// Counts of the ways in which sequences were decomposed during rewrites.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ReduceStats {
+    /// Sequences with liberal separation, split into properly separated and trailing ones.
+    pub liberal: u64,
+    /// Sequences with trailing separation.
+    pub trailing: u64,
+    /// Sequences that may be empty.
+    pub epsilon: u64,
+    /// Sequences of one or more elements, which are rewritten into recursive rules.
+    pub unbounded: u64,
+    /// Sequences of exactly one element, or of two elements with proper separation.
+    pub small_block: u64,
+    /// Sequences with a fixed number of elements, split into two shorter ones.
+    pub block: u64,
+    /// Sequences with a range of lengths, split into shorter ones.
+    pub span: u64,
+}
+
+/// Counts of the symbols and rules generated by rewrites.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RewriteStats {
+    /// Helper symbols allocated for parts of sequences.
+    pub aux_symbols: usize,
+    /// Grammar rules generated, including rules that `try_rewrite` discarded.
+    pub rules_emitted: usize,
+    /// Symbols on the RHS of generated rules, including rules that `try_rewrite` discarded.
+    pub symbols_emitted: usize,
+}
+
+/// Passes rules to the destination, or stores them in a buffer.
+struct Emitter<'a, D> where D: RuleContainer + 'a {
+    destination: &'a mut D,
+    buffer: Option<&'a mut Vec<(D::Symbol, Vec<D::Symbol>, D::History)>>,
+    rules_emitted: &'a mut usize,
+    symbols_emitted: &'a mut usize,
+}
+
+/// A sequence rule without its LHS and history. Identifies a symbol that derives the sequence.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
-struct PartialSequence<S> {
-    rhs: S,
-    start: u32,
-    end: Option<u32>,
-    separator: Separator<S>,
+pub struct PartialSequence<S> {
+    /// The sequence's element.
+    pub rhs: S,
+    /// The minimum number of repetitions.
+    pub start: u32,
+    /// Either the inclusive maximum number of repetitions, or `None` if the number of repetitions
+    /// is unlimited.
+    pub end: Option<u32>,
+    /// The way elements are separated in the sequence.
+    pub separator: Separator<S>,
 }
 
+/// Identifies a helper symbol. The rules of helpers for unlimited repetitions depend on the
+/// direction of recursion, so the key includes it. Other helpers have `None`.
+type HelperKey<S> = (PartialSequence<S>, Option<Recursion>);
+
 impl<'a, H, S> SequenceDestination<H> for &'a mut Vec<Sequence<H, S>> where S: GrammarSymbol {
     type Symbol = S;
 
@@ -43,7 +218,36 @@ impl<'a, H, S> SequenceDestination<H> for &'a mut Vec<Sequence<H, S>> where S: G
     }
 }
 
-impl<H, S, D> SequenceDestination<H> for SequencesToProductions<H, D> where
+/// Only trait objects are destinations, since the symbol type of a generic closure would be
+/// unconstrained. Wrap closures in `FnDestination` to avoid the coercion.
+impl<'a, H, S> SequenceDestination<H> for &'a mut FnMut(Sequence<H, S>) {
+    type Symbol = S;
+
+    fn add_sequence(&mut self, seq: Sequence<H, Self::Symbol>) {
+        self(seq);
+    }
+}
+
+impl<F, S> FnDestination<F, S> {
+    /// Creates a destination that calls `callback` with each sequence rule.
+    pub fn new(callback: F) -> Self {
+        FnDestination {
+            callback: callback,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<H, S, F> SequenceDestination<H> for FnDestination<F, S> where
+            F: FnMut(Sequence<H, S>) {
+    type Symbol = S;
+
+    fn add_sequence(&mut self, seq: Sequence<H, Self::Symbol>) {
+        (self.callback)(seq);
+    }
+}
+
+impl<'c, H, S, D> SequenceDestination<H> for SequencesToProductions<'c, H, D> where
             D: RuleContainer<History=H::Rewritten, Symbol=S>,
             H: RewriteSequence,
             H::Rewritten: Clone,
@@ -53,51 +257,400 @@ impl<H, S, D> SequenceDestination<H> for SequencesToProductions<H, D> where
     fn add_sequence(&mut self, seq: Sequence<H, Self::Symbol>) {
         self.rewrite(seq);
     }
+
+    fn add_sequence_with_recursion(&mut self,
+                                   seq: Sequence<H, Self::Symbol>,
+                                   recursion: Recursion) {
+        self.rewrite_with_recursion(seq, recursion);
+    }
+}
+
+impl<'a, 'c, H, Ss> SequencesToProductions<'c, H, RuleVec<H::Rewritten, &'a mut Ss>> where
+            H: RewriteSequence,
+            H::Rewritten: Clone,
+            Ss: SymbolSource {
+    /// Rewrites a sequence rule into grammar rules, and returns them instead of inserting them
+    /// into a destination. Helper symbols are allocated from `sym_source`.
+    pub fn expand(seq: Sequence<H, Ss::Symbol>, sym_source: &'a mut Ss)
+                -> Vec<Rule<H::Rewritten, Ss::Symbol>> {
+        let mut rewriter = SequencesToProductions::new(RuleVec::with_sym_source(sym_source));
+        rewriter.rewrite(seq);
+        rewriter.destination.into_rules()
+    }
 }
 
-impl<H, S, D> SequencesToProductions<H, D> where
+impl<'c, H, S, D> SequencesToProductions<'c, H, D> where
             D: RuleContainer<History=H::Rewritten, Symbol=S>,
             H: RewriteSequence,
             H::Rewritten: Clone,
             S: GrammarSymbol {
+    /// Creates a rewriter that inserts grammar rules into `destination`.
     pub fn new(destination: D) -> Self {
+        SequencesToProductions::with_map(destination, HashMap::new())
+    }
+
+    /// Creates a rewriter that inserts grammar rules into `destination`, and uses the symbols
+    /// in `map` for the sequences they derive. No rules are generated for these symbols.
+    ///
+    /// Symbols in `map` must come from the destination's symbol source, and each of them must
+    /// derive the sequence identified by its key.
+    pub fn with_map(destination: D, map: HashMap<PartialSequence<S>, S>) -> Self {
         SequencesToProductions {
             destination: destination,
             stack: vec![],
-            map: HashMap::new(),
+            top_lhs: None,
+            map: map.into_iter().map(|(partial, sym)| ((partial, None), sym)).collect(),
+            allocated: vec![],
+            separator_strings: HashMap::new(),
+            separator_syms: HashMap::new(),
+            pending_separators: HashSet::new(),
+            emitted_separators: vec![],
+            parents: HashMap::new(),
+            treat_max_as_unbounded: false,
+            right_linear: false,
+            max_rules: None,
+            max_symbols: None,
+            limits_from: None,
+            exceeded: None,
+            share_helpers: false,
+            emit_grouped: false,
+            recursion: Recursion::Left,
+            expansion: ExpansionStrategy::Balanced,
+            nullable: HashSet::new(),
+            nullable_separator: NullableSeparator::Allow,
+            buffer: vec![],
+            on_new_symbol: None,
+            stats: ReduceStats::default(),
+            rewrite_stats: RewriteStats::default(),
+            liberal_parts: HashMap::new(),
+        }
+    }
+
+    /// Sets whether an inclusive maximum of `u32::MAX` repetitions is treated as unlimited. By
+    /// default, such sequences are rewritten as bounded ones, which takes many rules.
+    pub fn treat_max_as_unbounded(mut self, enabled: bool) -> Self {
+        self.treat_max_as_unbounded = enabled;
+        self
+    }
+
+    /// Sets whether sequences without separators are rewritten into right-linear rules, such as
+    /// `seq ::= epsilon | item seq`. Sequences with bounds get a chain of helper symbols, one
+    /// for each count of remaining elements. The result can be turned into a finite automaton.
+    /// By default, sequences are split in halves, and recursion is to the left.
+    pub fn right_linear(mut self, enabled: bool) -> Self {
+        self.right_linear = enabled;
+        self
+    }
+
+    /// Limits the number of rules that `try_rewrite` may generate for a single sequence rule.
+    /// By default, there's no limit.
+    pub fn set_max_rules(&mut self, max_rules: Option<usize>) {
+        self.max_rules = max_rules;
+    }
+
+    /// Limits the total number of symbols on the RHS of rules that `try_rewrite` may generate
+    /// for a single sequence rule. By default, there's no limit.
+    pub fn set_max_symbols(&mut self, max_symbols: Option<usize>) {
+        self.max_symbols = max_symbols;
+    }
+
+    /// Sets whether helper symbols are shared between all sequences rewritten by this rewriter.
+    /// By default, each rewrite allocates its own helpers, even if another sequence already has
+    /// a helper for the same partial sequence.
+    pub fn share_helpers(mut self, enabled: bool) -> Self {
+        self.share_helpers = enabled;
+        self
+    }
+
+    /// Sets whether the rules for each sequence are emitted grouped by their LHS, with the
+    /// sequence's own rules first, followed by the rules of helper symbols in the order in which
+    /// the symbols were allocated. Otherwise, rules are emitted as soon as they are generated.
+    pub fn set_emit_grouped(&mut self, enabled: bool) {
+        self.emit_grouped = enabled;
+    }
+
+    /// Sets the direction of recursion for sequences that don't specify one. By default,
+    /// recursion is to the left.
+    pub fn set_recursion(&mut self, recursion: Recursion) {
+        self.recursion = recursion;
+    }
+
+    /// Sets the way in which sequences with a bounded number of elements are rewritten. By
+    /// default, they are split in balanced halves.
+    pub fn set_expansion_strategy(&mut self, expansion: ExpansionStrategy) {
+        self.expansion = expansion;
+    }
+
+    /// Tells the rewriter which symbols are nullable. Only separators are checked against these
+    /// symbols. A separator string is nullable if all of its symbols are.
+    pub fn set_nullable_symbols(&mut self, nullable: &[S]) {
+        self.nullable = nullable.iter().cloned().collect();
+    }
+
+    /// Sets the treatment of nullable separators. By default, they are allowed.
+    pub fn set_nullable_separator(&mut self, treatment: NullableSeparator) {
+        self.nullable_separator = treatment;
+    }
+
+    /// Sets the direction of recursion for sequences that don't specify one, in the builder
+    /// style. See `set_recursion`.
+    pub fn recursion(mut self, recursion: Recursion) -> Self {
+        self.recursion = recursion;
+        self
+    }
+
+    /// Returns a new symbol that derives a separator made of several symbols, such as `", "`.
+    /// The symbol can be used in any kind of separation. Its rule `sep ::= syms` is generated
+    /// along with the rules of the first sequence that uses it, with a history from `no_op`.
+    /// The same string always gets the same symbol.
+    pub fn separator_string(&mut self, syms: &[S]) -> S {
+        if let Some(&sep) = self.separator_syms.get(syms) {
+            return sep;
         }
+        let sep = self.destination.next_sym(false);
+        self.separator_syms.insert(syms.to_vec(), sep);
+        self.separator_strings.insert(sep, syms.to_vec());
+        self.pending_separators.insert(sep);
+        sep
+    }
+
+    /// Registers a callback that is invoked whenever a helper symbol is allocated for a sequence
+    /// that doesn't have a symbol yet. The callback may borrow data that outlives the rewriter.
+    pub fn on_new_symbol<F>(&mut self, callback: F) where
+                F: FnMut(S, &PartialSequence<S>) + 'c {
+        self.on_new_symbol = Some(Box::new(callback));
+    }
+
+    /// Returns the counts of decompositions done by all rewrites so far.
+    pub fn reduce_stats(&self) -> ReduceStats {
+        self.stats
+    }
+
+    /// Returns the counts of symbols and rules generated by all rewrites so far.
+    pub fn rewrite_stats(&self) -> RewriteStats {
+        self.rewrite_stats
+    }
+
+    /// Returns the symbols that derive the parts of a sequence with liberal separation, which was
+    /// rewritten with `lhs` on its LHS.
+    pub fn liberal_parts(&self, lhs: S) -> Option<LiberalParts<S>> {
+        self.liberal_parts.get(&lhs).cloned()
+    }
+
+    /// Returns the range of repetitions of the sequence that the helper symbol `sym` derives.
+    /// Helpers are known until the next call to `rewrite`, or for good if helpers are shared, as
+    /// are the symbols passed to `with_map`.
+    pub fn helper_bounds(&self, sym: S) -> Option<(u32, Option<u32>)> {
+        self.map.iter().find(|&(_, &helper)| helper == sym).map(|(&(ref partial, _), _)| {
+            (partial.start, partial.end)
+        })
+    }
+
+    /// Returns the helper symbols that are referred to by rules of more than one LHS, in the
+    /// order of their IDs. Like `helper_bounds`, this covers helpers that are still known.
+    pub fn shared_helpers(&self) -> Vec<S> {
+        let mut shared = self.parents.iter().filter(|&(_, parents)| parents.len() > 1)
+                                            .map(|(&helper, _)| helper)
+                                            .collect::<Vec<_>>();
+        shared.sort();
+        shared
     }
 
+    /// Rewrites all sequence rules into grammar rules, which are inserted into `rules`.
     pub fn rewrite_sequences(sequence_rules: &[Sequence<H, S>], rules: D) {
-        let mut rewrite = SequenceRuleBuilder::new(SequencesToProductions::new(rules));
+        SequencesToProductions::rewrite_sequences_with(sequence_rules, rules, false);
+    }
+
+    /// Rewrites all sequence rules into grammar rules, which are inserted into `rules`. Helper
+    /// symbols are shared between the sequences, so identical parts of sequences are derived by
+    /// the same rules.
+    pub fn rewrite_sequences_shared(sequence_rules: &[Sequence<H, S>], rules: D) {
+        SequencesToProductions::rewrite_sequences_with(sequence_rules, rules, true);
+    }
+
+    fn rewrite_sequences_with(sequence_rules: &[Sequence<H, S>], rules: D, share_helpers: bool) {
+        let rewriter = SequencesToProductions::new(rules).share_helpers(share_helpers);
+        let mut rewrite = SequenceRuleBuilder::new(rewriter);
         for rule in sequence_rules {
             rewrite = rewrite.sequence(rule.lhs)
                              .separator(rule.separator)
-                             .inclusive(rule.start, rule.end)
-                             .rhs_with_history(rule.rhs, &rule.history);
+                             .inclusive(rule.start, rule.end);
+            rewrite = rewrite.rhs_with_history(rule.rhs, &rule.history);
         }
     }
 
+    /// Rewrites a sequence rule into grammar rules.
     pub fn rewrite(&mut self, top: Sequence<H, S>) {
+        let top_lhs = top.lhs;
+        self.begin_rewrite(top);
+
+        while let Some(seq) = self.stack.pop() {
+            assert!(seq.start <= seq.end.unwrap_or(!0));
+            self.reduce(seq);
+        }
+
+        if self.emit_grouped {
+            self.flush(top_lhs);
+        }
+    }
+
+    /// Rewrites a sequence rule into grammar rules, with the given direction of recursion instead
+    /// of the one set with `set_recursion`.
+    pub fn rewrite_with_recursion(&mut self, top: Sequence<H, S>, recursion: Recursion) {
+        let default = mem::replace(&mut self.recursion, recursion);
+        self.rewrite(top);
+        self.recursion = default;
+    }
+
+    /// Rewrites a sequence rule into grammar rules. Fails if the bounds are invalid, or if the
+    /// rewrite would exceed the limits set with `set_max_rules` and `set_max_symbols`. Limits
+    /// are checked before each rule is generated, so a failing rewrite stops early. On failure,
+    /// no rules are inserted into the destination, although helper symbols may be allocated.
+    /// Such helpers are never reused, even if helpers are shared.
+    pub fn try_rewrite(&mut self, top: Sequence<H, S>) -> Result<(), SequenceError<S>> {
+        let (top_lhs, start, end) = (top.lhs, top.start, top.end);
+        let error = |kind| SequenceError { lhs: top_lhs, start: start, end: end, kind: kind };
+        if start > end.unwrap_or(!0) {
+            return Err(error(SequenceErrorKind::InvalidBounds));
+        }
+        if self.rejects(top.separator) {
+            return Err(error(SequenceErrorKind::NullableSeparator));
+        }
+        self.begin_rewrite(top);
+        self.buffer.clear();
+
+        // Rules are buffered until the rewrite succeeds.
+        let emit_grouped = mem::replace(&mut self.emit_grouped, true);
+        self.limits_from = Some((self.rewrite_stats.rules_emitted,
+                                 self.rewrite_stats.symbols_emitted));
+        while let Some(seq) = self.stack.pop() {
+            self.reduce(seq);
+            if let Some(kind) = self.exceeded.take().or_else(|| self.exceeds_limits(0, 0)) {
+                self.emit_grouped = emit_grouped;
+                self.limits_from = None;
+                self.stack.clear();
+                self.buffer.clear();
+                self.discard_helpers(0, 0);
+                return Err(error(kind));
+            }
+        }
+        self.emit_grouped = emit_grouped;
+        self.limits_from = None;
+
+        if self.emit_grouped {
+            self.flush(top_lhs);
+        } else {
+            for (lhs, rhs, history) in mem::replace(&mut self.buffer, vec![]) {
+                self.destination.add_rule(lhs, &rhs[..], history);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the limit that the current `try_rewrite` would exceed by generating `rules` more
+    /// rules with `symbols` more symbols in total.
+    fn exceeds_limits(&self, rules: u64, symbols: u64) -> Option<SequenceErrorKind> {
+        let (rules_from, symbols_from) = match self.limits_from {
+            Some(counts) => counts,
+            None => return None,
+        };
+        let rules = (self.rewrite_stats.rules_emitted - rules_from) as u64 + rules;
+        let symbols = (self.rewrite_stats.symbols_emitted - symbols_from) as u64 + symbols;
+        match (self.max_rules, self.max_symbols) {
+            (Some(max_rules), _) if rules > max_rules as u64 => {
+                Some(SequenceErrorKind::TooManyRules(max_rules))
+            }
+            (_, Some(max_symbols)) if symbols > max_symbols as u64 => {
+                Some(SequenceErrorKind::TooManySymbols(max_symbols))
+            }
+            _ => None
+        }
+    }
+
+    /// Inserts buffered rules into the destination, grouped by their LHS.
+    fn flush(&mut self, top_lhs: S) {
+        let mut order = vec![top_lhs];
+        order.extend(self.allocated.iter().map(|partial| self.map[partial]));
+        let mut rules = mem::replace(&mut self.buffer, vec![]);
+        rules.sort_by_key(|&(lhs, _, _)| order.iter().position(|&sym| sym == lhs));
+        for (lhs, rhs, history) in rules {
+            self.destination.add_rule(lhs, &rhs[..], history);
+        }
+    }
+
+    /// Returns an iterator that rewrites a sequence rule into grammar rules step by step. Rules
+    /// are yielded instead of being inserted into the destination. Helper symbols are allocated
+    /// as the iterator advances. If the iterator is dropped early, its helpers are forgotten.
+    pub fn rewrite_iter<'a>(&'a mut self, top: Sequence<H, S>) -> RewriteIter<'a, 'c, H, D> {
+        self.begin_rewrite(top);
+        self.buffer.clear();
+        RewriteIter { rewriter: self }
+    }
+
+    fn begin_rewrite(&mut self, top: Sequence<H, S>) {
         self.stack.clear();
-        self.map.clear();
+        self.emitted_separators.clear();
+        for partial in self.allocated.drain(..) {
+            if !self.share_helpers {
+                if let Some(helper) = self.map.remove(&partial) {
+                    self.parents.remove(&helper);
+                }
+            }
+        }
+        assert!(!self.rejects(top.separator), "the separator of a sequence is nullable");
+        let separator = match top.separator {
+            Liberal(sep) if self.nullable_separator == NullableSeparator::Disambiguate
+                         && self.is_nullable_separator(sep) => Trailing(sep),
+            separator => separator,
+        };
+        self.top_lhs = Some(top.lhs);
         self.stack.push(Sequence {
             lhs: top.lhs,
             rhs: top.rhs,
             start: top.start,
             end: top.end,
-            separator: top.separator,
+            separator: separator,
             history: top.history.sequence(&top),
         });
+    }
 
-        while let Some(seq) = self.stack.pop() {
-            assert!(seq.start <= seq.end.unwrap_or(!0));
-            self.reduce(seq);
+    fn is_nullable_separator(&self, sep: S) -> bool {
+        match self.separator_strings.get(&sep) {
+            Some(string) => string.iter().all(|sym| self.nullable.contains(sym)),
+            None => self.nullable.contains(&sep),
         }
     }
 
-    fn rule(&mut self, lhs: S) -> RuleBuilder<&mut D> {
-        RuleBuilder::new(&mut self.destination).rule(lhs)
+    fn rejects(&self, separator: Separator<S>) -> bool {
+        self.nullable_separator == NullableSeparator::Reject
+            && separator.symbol().map_or(false, |sep| self.is_nullable_separator(sep))
+    }
+
+    /// Runs a single step of the rewrite, and returns the rules that the step generates for the
+    /// sequence. Nothing is inserted into the destination. The returned rules may refer to helper
+    /// symbols, which are allocated, but have no rules. These helpers are forgotten afterwards, so
+    /// later rewrites never reuse them.
+    pub fn reduce_once(&mut self, seq: Sequence<H::Rewritten, S>) -> Vec<(S, Vec<S>)> {
+        let emit_grouped = mem::replace(&mut self.emit_grouped, true);
+        let stack_len = self.stack.len();
+        let buffer_len = self.buffer.len();
+        let allocated_len = self.allocated.len();
+        let emitted_len = self.emitted_separators.len();
+        self.reduce(seq);
+        self.stack.truncate(stack_len);
+        self.discard_helpers(allocated_len, emitted_len);
+        self.emit_grouped = emit_grouped;
+        self.buffer.drain(buffer_len..).map(|(lhs, rhs, _)| (lhs, rhs)).collect()
+    }
+
+    fn rule(&mut self, lhs: S) -> RuleBuilder<Emitter<D>> {
+        let buffer = if self.emit_grouped { Some(&mut self.buffer) } else { None };
+        RuleBuilder::new(Emitter {
+            destination: &mut self.destination,
+            buffer: buffer,
+            rules_emitted: &mut self.rewrite_stats.rules_emitted,
+            symbols_emitted: &mut self.rewrite_stats.symbols_emitted,
+        }).rule(lhs)
     }
 
     fn recurse(&mut self, seq: Sequence<&H::Rewritten, S>) -> S {
@@ -107,48 +660,121 @@ impl<H, S, D> SequencesToProductions<H, D> where
             rhs: seq.rhs,
             separator: seq.separator,
             start: seq.start,
-            end: seq.end
+            end: seq.end,
+        };
+        // Symbols from a map given to `with_map` derive the sequence in either direction.
+        let key = if seq.end.is_none() && !self.map.contains_key(&(partial.clone(), None)) {
+            (partial, Some(self.recursion))
+        } else {
+            (partial, None)
         };
 
-        match self.map.entry(partial) {
+        let helper = match self.map.entry(key) {
             Entry::Vacant(vacant) => {
                 let lhs = sym_source.sym();
+                if sym_source.has_names() {
+                    let name = helper_name(&*sym_source, &vacant.key().0);
+                    sym_source.set_name(lhs, &name);
+                }
+                self.rewrite_stats.aux_symbols += 1;
+                if let Some(ref mut callback) = self.on_new_symbol {
+                    callback(lhs, &vacant.key().0);
+                }
+                self.allocated.push(vacant.key().clone());
                 vacant.insert(lhs);
+                let origin = SequenceOrigin {
+                    lhs: self.top_lhs.unwrap_or(seq.lhs),
+                    start: seq.start,
+                    end: seq.end,
+                    separator: seq.separator,
+                };
                 self.stack.push(Sequence {
                     lhs: lhs,
                     rhs: seq.rhs,
                     start: seq.start,
                     end: seq.end,
                     separator: seq.separator,
-                    history: seq.history.no_op(),
+                    history: seq.history.sequence_part(&origin),
                 });
                 lhs
             }
             Entry::Occupied(lhs) => {
                 *lhs.get()
             }
-        }
+        };
+        self.parents.entry(helper).or_insert_with(|| HashSet::new()).insert(seq.lhs);
+        helper
     }
 
     fn reduce(&mut self, sequence: Sequence<H::Rewritten, S>) {
-        let Sequence { lhs, rhs, start, end, separator, ref history } = sequence;
+        let Sequence { lhs, rhs, start, mut end, separator, ref history } = sequence;
+        if self.treat_max_as_unbounded && end == Some(u32::MAX) {
+            end = None;
+        }
         let sequence = Sequence { lhs: lhs, rhs: rhs, start: start, end: end,
             separator: separator, history: history };
+        if let Some(sep) = separator.symbol() {
+            if self.pending_separators.remove(&sep) {
+                let string = self.separator_strings[&sep].clone();
+                self.rule(sep).rhs_with_history(string, history.no_op());
+                self.emitted_separators.push(sep);
+            }
+        }
 
         match (separator, start, end) {
+            // The empty sequence is split off first, so that the parts of a liberal sequence and
+            // the proper sequence inside a trailing one are never nullable. Otherwise, a lone
+            // separator would be accepted, and the empty string would have two derivations.
+            (Liberal(_), 0, Some(0)) => {
+                self.stats.epsilon += 1;
+                self.rule(lhs).rhs_with_history([], history.clone());
+            }
             (Liberal(sep), _, _) => {
-                let sym1 = self.recurse(sequence.clone().separator(Proper(sep)));
-                let sym2 = self.recurse(sequence.clone().separator(Trailing(sep)));
+                self.stats.liberal += 1;
+                let sequence = if start == 0 {
+                    self.stats.epsilon += 1;
+                    // seq ::= epsilon
+                    self.rule(lhs).rhs_with_history([], history.clone());
+                    sequence.inclusive(1, end)
+                } else {
+                    sequence
+                };
+                let sym1 = self.recurse(sequence.clone().with_separator(Proper(sep)));
+                let sym2 = self.recurse(sequence.clone().with_separator(Trailing(sep)));
+                self.liberal_parts.insert(lhs, LiberalParts { proper: sym1, trailing: sym2 });
                 // seq ::= sym1 | sym2
                 self.rule(lhs).rhs_with_history([sym1], history.clone())
                               .rhs_with_history([sym2], history.clone());
             }
-            (Trailing(sep), _, _) => {
-                let sym = self.recurse(sequence.separator(Proper(sep)));
-                // seq ::= sym sep
-                self.rule(lhs).rhs_with_history([sym, sep], history.clone());
+            (Separator::Null, start, end) if self.right_linear => {
+                if start == 0 {
+                    self.stats.epsilon += 1;
+                    // seq ::= epsilon
+                    self.rule(lhs).rhs_with_history([], history.clone());
+                }
+                if end == None {
+                    self.stats.unbounded += 1;
+                } else if Some(start) == end {
+                    self.stats.block += 1;
+                } else {
+                    self.stats.span += 1;
+                }
+                let rest = match (start, end) {
+                    (_, Some(0)) => None,
+                    // seq ::= item seq
+                    (0, None) => Some(lhs),
+                    (start, end) => {
+                        let start = start.saturating_sub(1);
+                        Some(self.recurse(sequence.inclusive(start, end.map(|end| end - 1))))
+                    }
+                };
+                if let Some(rest) = rest {
+                    // seq ::= item rest
+                    self.rule(lhs).rhs_with_history([rhs, rest], history.clone());
+                }
             }
             (_, 0, end) => {
+                self.stats.epsilon += 1;
                 // seq ::= epsilon | sym
                 self.rule(lhs).rhs_with_history([], history.clone());
                 if end != Some(0) {
@@ -156,54 +782,101 @@ impl<H, S, D> SequencesToProductions<H, D> where
                     self.rule(lhs).rhs_with_history([sym], history.clone());
                 }
             }
+            (Trailing(sep), _, _) => {
+                self.stats.trailing += 1;
+                let sym = self.recurse(sequence.with_separator(Proper(sep)));
+                // seq ::= sym sep
+                self.rule(lhs).rhs_with_history([sym, sep], history.clone());
+            }
             (separator, 1, None) => {
+                self.stats.unbounded += 1;
                 // seq ::= item
                 self.rule(lhs).rhs_with_history([rhs], history.clone());
-                // Left recursive
-                // seq ::= seq sep item
-                if let Separator::Proper(sep) = separator {
-                    self.rule(lhs).rhs_with_history([lhs, sep, rhs], history.clone());
-                } else {
-                    self.rule(lhs).rhs_with_history([lhs, rhs], history.clone());
+                match (separator, self.recursion) {
+                    // Left recursive
+                    // seq ::= seq sep item
+                    (Separator::Proper(sep), Recursion::Left) => {
+                        self.rule(lhs).rhs_with_history([lhs, sep, rhs], history.clone());
+                    }
+                    (_, Recursion::Left) => {
+                        self.rule(lhs).rhs_with_history([lhs, rhs], history.clone());
+                    }
+                    // Right recursive
+                    // seq ::= item sep seq
+                    (Separator::Proper(sep), Recursion::Right) => {
+                        self.rule(lhs).rhs_with_history([rhs, sep, lhs], history.clone());
+                    }
+                    (_, Recursion::Right) => {
+                        self.rule(lhs).rhs_with_history([rhs, lhs], history.clone());
+                    }
                 }
             }
             (_, 1, Some(1)) => {
+                self.stats.small_block += 1;
                 self.rule(lhs).rhs_with_history([rhs], history.clone());
             }
-            (_, 1, Some(2)) => {
-                let sym1 = self.recurse(sequence.clone().inclusive(1, Some(1)));
-                let sym2 = self.recurse(sequence.clone().inclusive(2, Some(2)));
+            (separator, start, end) if self.expansion == ExpansionStrategy::Linear => {
+                let sep = separator.symbol();
+                match end {
+                    Some(end) => {
+                        if start == end {
+                            self.stats.block += 1;
+                        } else {
+                            self.stats.span += 1;
+                        }
+                        // Counted in u64, so that an `end` of `u32::MAX` doesn't overflow.
+                        for len in start as u64 .. end as u64 + 1 {
+                            // Checked before spelling out, which takes time for long rules.
+                            let num_seps = if sep.is_some() { len.saturating_sub(1) } else { 0 };
+                            if let Some(kind) = self.exceeds_limits(1, len + num_seps) {
+                                self.exceeded = Some(kind);
+                                return;
+                            }
+                            // seq ::= item sep item ... item
+                            let string = spelled_out(rhs, sep, len as u32);
+                            self.rule(lhs).rhs_with_history(string, history.clone());
+                        }
+                    }
+                    None => {
+                        self.stats.span += 1;
+                        // seq ::= item sep ... item sep sym
+                        let mut string = spelled_out(rhs, sep, start - 1);
+                        string.extend(sep);
+                        string.push(self.recurse(sequence.inclusive(1, None)));
+                        self.rule(lhs).rhs_with_history(string, history.clone());
+                    }
+                }
+            }
+            (_, 1, Some(end)) => {
+                self.stats.span += 1;
+                let pow2 = lower_power_of_two(end);
+                let sym1 = self.recurse(sequence.clone().inclusive(1, Some(pow2)));
+                let sym2 = self.recurse(sequence.clone().inclusive(pow2 + 1, Some(end)));
                 // seq ::= sym1 | sym2
                 self.rule(lhs).rhs_with_history([sym1], history.clone())
                               .rhs_with_history([sym2], history.clone());
             }
-            (separator, 1, Some(end)) => {
-                let pow2 = end.next_power_of_two() / 2;
-                let (seq1, seq2) = (sequence.clone().inclusive(start, Some(pow2)),
-                                    sequence.clone().inclusive(start, Some(end - pow2)));
-                let rhs = &[self.recurse(seq1.separator(separator.prefix_separator())),
-                            self.recurse(seq2.separator(separator))];
-                // seq ::= sym1 sym2
-                self.rule(lhs).rhs_with_history(rhs, history.clone());
-            }
             // Bug in rustc. Must use comparison.
             (Separator::Proper(sep), start, end) if start == 2 && end == Some(2) => {
+                self.stats.small_block += 1;
                 self.rule(lhs).rhs_with_history([rhs, sep, rhs], history.clone());
             }
             (separator, 2 ... 0xFFFF_FFFF, end) => {
                 // to do infinity
                 let (seq1, seq2) = if Some(start) == end {
                     // A "block"
-                    let pow2 = start.next_power_of_two() / 2;
+                    self.stats.block += 1;
+                    let pow2 = lower_power_of_two(start);
                     (sequence.clone().inclusive(pow2, Some(pow2)),
                      sequence.clone().inclusive(start - pow2, Some(start - pow2)))
                 } else {
                     // A "span"
-                    (sequence.clone().inclusive(start, Some(start)),
-                     sequence.clone().inclusive(1, end.map(|n| n - start - 1)))
+                    self.stats.span += 1;
+                    (sequence.clone().inclusive(start - 1, Some(start - 1)),
+                     sequence.clone().inclusive(1, end.map(|n| n - start + 1)))
                 };
-                let rhs = &[self.recurse(seq1.separator(separator.prefix_separator())),
-                            self.recurse(seq2.separator(separator))];
+                let rhs = &[self.recurse(seq1.with_separator(separator.prefix_separator())),
+                            self.recurse(seq2.with_separator(separator))];
                 // seq ::= sym1 sym2
                 self.rule(lhs).rhs_with_history(rhs, history.clone());
             }
@@ -211,3 +884,150 @@ impl<H, S, D> SequencesToProductions<H, D> where
         }
     }
 }
+
+/// Returns the greatest power of two that is less than `n`, which must be at least 2. Unlike
+/// `n.next_power_of_two() / 2`, doesn't overflow for `n` above `2^31`.
+fn lower_power_of_two(n: u32) -> u32 {
+    1 << (31 - (n - 1).leading_zeros())
+}
+
+/// Returns `len` elements, separated by `sep`.
+fn spelled_out<S>(item: S, sep: Option<S>, len: u32) -> Vec<S> where S: Copy {
+    let mut string = vec![];
+    for i in 0 .. len {
+        if i != 0 {
+            string.extend(sep);
+        }
+        string.push(item);
+    }
+    string
+}
+
+impl<'c, H, D> SequencesToProductions<'c, H, D> where
+            H: RewriteSequence,
+            D: RuleContainer {
+    /// Forgets the helper symbols allocated during the current rewrite, starting with the
+    /// `from`th one. Their rules were discarded, so they must not be reused. Rules of separator
+    /// strings generated since the `separators_from`th one will be generated again.
+    fn discard_helpers(&mut self, from: usize, separators_from: usize) {
+        let pending = self.emitted_separators.drain(separators_from..);
+        self.pending_separators.extend(pending);
+        for partial in self.allocated.drain(from..) {
+            if let Some(helper) = self.map.remove(&partial) {
+                self.parents.remove(&helper);
+                self.liberal_parts.remove(&helper);
+                for parents in self.parents.values_mut() {
+                    parents.remove(&helper);
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'c, H, S, D> Iterator for RewriteIter<'a, 'c, H, D> where
+            D: RuleContainer<History=H::Rewritten, Symbol=S>,
+            H: RewriteSequence,
+            H::Rewritten: Clone,
+            S: GrammarSymbol {
+    type Item = Rule<H::Rewritten, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rewriter = &mut *self.rewriter;
+        loop {
+            if !rewriter.buffer.is_empty() {
+                let (lhs, rhs, history) = rewriter.buffer.remove(0);
+                return Some(Rule::new(lhs, rhs, history));
+            }
+            let seq = match rewriter.stack.pop() {
+                Some(seq) => seq,
+                None => return None,
+            };
+            assert!(seq.start <= seq.end.unwrap_or(!0));
+            // Generated rules go to the buffer.
+            let emit_grouped = mem::replace(&mut rewriter.emit_grouped, true);
+            rewriter.reduce(seq);
+            rewriter.emit_grouped = emit_grouped;
+        }
+    }
+}
+
+impl<'a, 'c, H, D> Drop for RewriteIter<'a, 'c, H, D> where
+            H: RewriteSequence,
+            D: RuleContainer {
+    fn drop(&mut self) {
+        // Rules that weren't pulled are discarded, along with the helpers they define.
+        if !self.rewriter.stack.is_empty() || !self.rewriter.buffer.is_empty() {
+            self.rewriter.discard_helpers(0, 0);
+        }
+        self.rewriter.stack.clear();
+        self.rewriter.buffer.clear();
+    }
+}
+
+/// Makes up a name for a helper symbol from the name of the element, such as `item{1,}` or
+/// `item{0,3} % comma`.
+fn helper_name<Ss>(sym_source: &Ss, seq: &PartialSequence<Ss::Symbol>) -> String where
+            Ss: SymbolSource,
+            Ss::Symbol: GrammarSymbol {
+    let name_of = |sym: Ss::Symbol| {
+        sym_source.name_of(sym).map_or_else(|| format!("s{}", sym.usize()), |name| name.to_string())
+    };
+    let end = seq.end.map_or(String::new(), |end| end.to_string());
+    format!("{}{{{},{}}}{}", name_of(seq.rhs), seq.start, end, seq.separator.pretty(&name_of))
+}
+
+impl<'a, D> SymbolSource for Emitter<'a, D> where D: RuleContainer {
+    type Symbol = D::Symbol;
+
+    fn next_sym(&mut self, terminal: bool) -> Self::Symbol {
+        self.destination.next_sym(terminal)
+    }
+
+    fn mark_as_nonterminal(&mut self, sym: Self::Symbol) {
+        self.destination.mark_as_nonterminal(sym)
+    }
+
+    fn start_sym(&self) -> Self::Symbol {
+        self.destination.start_sym()
+    }
+
+    fn num_syms(&self) -> usize {
+        self.destination.num_syms()
+    }
+
+    fn has_names(&self) -> bool {
+        self.destination.has_names()
+    }
+
+    fn set_name(&mut self, sym: Self::Symbol, name: &str) {
+        self.destination.set_name(sym, name)
+    }
+
+    fn name_of(&self, sym: Self::Symbol) -> Option<&str> {
+        self.destination.name_of(sym)
+    }
+
+    fn sym_named(&mut self, name: &str) -> Self::Symbol {
+        self.destination.sym_named(name)
+    }
+}
+
+impl<'a, D> RuleContainer for Emitter<'a, D> where D: RuleContainer {
+    type History = D::History;
+
+    fn retain<F>(&mut self, f: F) where
+                F: FnMut(Self::Symbol, &[Self::Symbol], &Self::History) -> bool {
+        self.destination.retain(f);
+    }
+
+    fn add_rule(&mut self, lhs: Self::Symbol,
+                           rhs: &[Self::Symbol],
+                           history: Self::History) {
+        *self.rules_emitted += 1;
+        *self.symbols_emitted += rhs.len();
+        match self.buffer {
+            Some(ref mut buffer) => buffer.push((lhs, rhs.to_vec(), history)),
+            None => self.destination.add_rule(lhs, rhs, history),
+        }
+    }
+}
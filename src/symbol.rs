@@ -1,8 +1,19 @@
 //! Symbol types can be used to parameterize grammars.
 
 use core::nonzero::NonZero;
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::btree_map;
+use std::cmp;
 use std::convert::{From, Into};
 use std::hash::Hash;
+use std::iter;
+use std::marker::PhantomData;
+
+use bit_vec::{self, BitVec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::de::Error;
 
 /// A numeric symbol type.
 #[derive(Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd)]
@@ -34,6 +45,25 @@ impl Into<u64> for NumericSymbol {
 
 impl GrammarSymbol for NumericSymbol {}
 
+#[cfg(feature = "serde")]
+impl Serialize for NumericSymbol {
+    fn serialize<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error> where Sr: Serializer {
+        serializer.serialize_u32(*self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for NumericSymbol {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        let id = try!(NumericSymbolRepr::deserialize(deserializer));
+        if id == 0 {
+            Err(D::Error::custom("symbol IDs are nonzero"))
+        } else {
+            Ok(NumericSymbol::from(id as u64))
+        }
+    }
+}
+
 /// A source of symbols.
 pub trait SymbolSource {
     /// The type of symbols generated by this source.
@@ -60,6 +90,23 @@ pub trait SymbolSource {
     fn sym<T>(&mut self) -> T where Self: Sized, T: SymbolContainer<Self::Symbol> {
         T::generate(self)
     }
+    /// Checks whether the source stores names of symbols. Names for generated symbols are only
+    /// made up if it does. Returns `false` by default.
+    fn has_names(&self) -> bool {
+        false
+    }
+    /// Associates a name with a symbol. Ignored by default.
+    fn set_name(&mut self, _sym: Self::Symbol, _name: &str) {}
+    /// Returns the name of a symbol. Returns `None` by default.
+    fn name_of(&self, _sym: Self::Symbol) -> Option<&str> {
+        None
+    }
+    /// Generates a new nonterminal symbol with the given name.
+    fn sym_named(&mut self, name: &str) -> Self::Symbol {
+        let sym = self.next_sym(false);
+        self.set_name(sym, name);
+        sym
+    }
 }
 
 /// A source of symbols that tracks whether a symbol is terminal or nonterminal.
@@ -75,6 +122,10 @@ impl<'a, S> SymbolSource for &'a mut S where S: SymbolSource {
     fn mark_as_nonterminal(&mut self, sym: Self::Symbol) { (**self).mark_as_nonterminal(sym) }
     fn num_syms(&self) -> usize { (**self).num_syms() }
     fn start_sym(&self) -> Self::Symbol { (**self).start_sym() }
+    fn has_names(&self) -> bool { (**self).has_names() }
+    fn set_name(&mut self, sym: Self::Symbol, name: &str) { (**self).set_name(sym, name) }
+    fn name_of(&self, sym: Self::Symbol) -> Option<&str> { (**self).name_of(sym) }
+    fn sym_named(&mut self, name: &str) -> Self::Symbol { (**self).sym_named(name) }
 }
 
 impl<'a, S> TerminalSymbolSet for &'a mut S where S: TerminalSymbolSet {
@@ -87,6 +138,71 @@ pub struct ConsecutiveSymbols {
     next_sym: NumericSymbolRepr,
 }
 
+/// A source of numeric symbols that records the classification of symbols into terminals and
+/// nonterminals.
+#[derive(Clone, Debug)]
+pub struct ClassifiedSymbols {
+    symbols: ConsecutiveSymbols,
+    terminals: BitVec,
+    /// The names of tokens given to `register_tokens`.
+    token_names: BTreeMap<NumericSymbol, &'static str>,
+}
+
+/// A source of numeric symbols, where a range of IDs is reserved for terminal symbols that
+/// represent bytes. These symbols are the only terminals. The byte `b` is represented by the
+/// symbol with ID `b + 2`, and other symbols have IDs above that range.
+///
+/// Bytes themselves can't be symbols, because symbols must be convertible from any `u64`.
+#[derive(Clone, Debug)]
+pub struct ByteSymbols {
+    symbols: ConsecutiveSymbols,
+}
+
+/// The role of a symbol in a grammar.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SymbolKind {
+    /// A symbol that has no rules.
+    Terminal,
+    /// A symbol that has rules.
+    Nonterminal,
+    /// A helper symbol, all of whose rules were generated by a rewrite and which occurs only in
+    /// generated rules.
+    Generated,
+}
+
+/// A source of numeric symbols, where some IDs are bound to names before allocation, for
+/// example to agree with the token IDs of an external lexer. Symbols allocated with `sym` skip
+/// reserved IDs.
+#[derive(Clone, Debug, Default)]
+pub struct NamedSymbols {
+    symbols: ConsecutiveSymbols,
+    reserved: BTreeSet<NumericSymbolRepr>,
+    names: BTreeMap<String, NumericSymbol>,
+    sym_names: BTreeMap<NumericSymbol, String>,
+}
+
+/// An error in the reservation of a symbol ID.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReserveError {
+    /// The name that was to be bound.
+    pub name: String,
+    /// The ID that was to be reserved.
+    pub id: u32,
+    /// The kind of the error.
+    pub kind: ReserveErrorKind,
+}
+
+/// The kinds of errors in reservations of symbol IDs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReserveErrorKind {
+    /// The ID is already reserved, or has already been allocated.
+    IdTaken,
+    /// The name is already bound to an ID.
+    NameTaken,
+    /// The ID can't represent a symbol, or belongs to the start symbol.
+    InvalidId,
+}
+
 /// Iterator for generating terminal symbols.
 pub struct Terminals<S> {
     source: S,
@@ -116,6 +232,12 @@ impl ConsecutiveSymbols {
     }
 }
 
+impl Default for ConsecutiveSymbols {
+    fn default() -> Self {
+        ConsecutiveSymbols::new()
+    }
+}
+
 impl SymbolSource for ConsecutiveSymbols {
     type Symbol = NumericSymbol;
 
@@ -140,6 +262,255 @@ impl SymbolSource for ConsecutiveSymbols {
     }
 }
 
+impl ClassifiedSymbols {
+    /// Creates a source of numeric symbols with an empty symbol space.
+    pub fn new() -> Self {
+        ClassifiedSymbols {
+            symbols: ConsecutiveSymbols::new(),
+            terminals: BitVec::from_elem(FIRST_SYMBOL as usize, false),
+            token_names: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the symbol of a token with the given ID. The symbol has the same ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is zero, which isn't the ID of any symbol.
+    pub fn token(id: u32) -> NumericSymbol {
+        assert!(id != 0, "symbol IDs are nonzero");
+        NumericSymbol::from(id as u64)
+    }
+
+    /// Allocates the symbols of tokens, such as the variants of an enum with explicit
+    /// discriminants, and marks them as terminals. Each token gets the symbol with its ID, so
+    /// the IDs of tokens and symbols are the same. The symbol space grows as needed, and symbols
+    /// between tokens are allocated as nonterminals. Returns the symbols by name.
+    ///
+    /// The names are also stored, and `name_of` returns them. Since generated symbols are never
+    /// named, `has_names` stays `false`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a token ID is zero, or if it's the ID of the start symbol.
+    pub fn register_tokens<I>(&mut self, tokens: I) -> BTreeMap<&'static str, NumericSymbol> where
+                I: IntoIterator<Item=(&'static str, u32)> {
+        let mut names = BTreeMap::new();
+        for (name, id) in tokens {
+            let sym = ClassifiedSymbols::token(id);
+            assert!(sym != self.start_sym(), "the start symbol can't be a token");
+            while self.num_syms() <= sym.usize() {
+                self.next_sym(false);
+            }
+            self.terminals.set(sym.usize(), true);
+            self.token_names.insert(sym, name);
+            names.insert(name, sym);
+        }
+        names
+    }
+
+    /// Marks the given symbols as terminals, and all other symbols as nonterminals. The symbol
+    /// space grows to include symbols that haven't been allocated yet, and the symbols allocated
+    /// along the way are nonterminals.
+    pub fn set_terminals(&mut self, terminals: &[NumericSymbol]) {
+        self.terminals.clear();
+        for &sym in terminals {
+            while self.num_syms() <= sym.usize() {
+                self.next_sym(false);
+            }
+            self.terminals.set(sym.usize(), true);
+        }
+    }
+}
+
+impl Default for ClassifiedSymbols {
+    fn default() -> Self {
+        ClassifiedSymbols::new()
+    }
+}
+
+impl SymbolSource for ClassifiedSymbols {
+    type Symbol = NumericSymbol;
+
+    fn next_sym(&mut self, terminal: bool) -> NumericSymbol {
+        let sym = self.symbols.next_sym(terminal);
+        self.terminals.push(terminal);
+        sym
+    }
+
+    fn mark_as_nonterminal(&mut self, sym: Self::Symbol) {
+        if sym.usize() < self.terminals.len() {
+            self.terminals.set(sym.usize(), false);
+        }
+    }
+
+    fn start_sym(&self) -> NumericSymbol {
+        self.symbols.start_sym()
+    }
+
+    fn num_syms(&self) -> usize {
+        self.symbols.num_syms()
+    }
+
+    /// Only tokens have names.
+    fn name_of(&self, sym: Self::Symbol) -> Option<&str> {
+        self.token_names.get(&sym).map(|&name| name)
+    }
+}
+
+impl TerminalSymbolSet for ClassifiedSymbols {
+    fn is_terminal(&self, sym: Self::Symbol) -> bool {
+        self.terminals.get(sym.usize()).unwrap_or(false)
+    }
+}
+
+impl ByteSymbols {
+    /// Creates a source of numeric symbols where only bytes are in use.
+    pub fn new() -> Self {
+        ByteSymbols {
+            symbols: ConsecutiveSymbols { next_sym: FIRST_SYMBOL + 256 },
+        }
+    }
+
+    /// Returns the terminal symbol that represents a byte.
+    pub fn byte(b: u8) -> NumericSymbol {
+        NumericSymbol::from((FIRST_SYMBOL + b as u32) as u64)
+    }
+
+    /// Returns the byte represented by a symbol, or `None` if the symbol doesn't represent one.
+    pub fn as_byte(sym: NumericSymbol) -> Option<u8> {
+        let id: u64 = sym.into();
+        if id >= FIRST_SYMBOL as u64 && id < FIRST_SYMBOL as u64 + 256 {
+            Some((id - FIRST_SYMBOL as u64) as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the terminal symbols that represent a string of bytes.
+    pub fn literal(bytes: &[u8]) -> Vec<NumericSymbol> {
+        bytes.iter().map(|&b| ByteSymbols::byte(b)).collect()
+    }
+}
+
+impl Default for ByteSymbols {
+    fn default() -> Self {
+        ByteSymbols::new()
+    }
+}
+
+impl SymbolSource for ByteSymbols {
+    type Symbol = NumericSymbol;
+
+    fn next_sym(&mut self, terminal: bool) -> NumericSymbol {
+        self.symbols.next_sym(terminal)
+    }
+
+    fn mark_as_nonterminal(&mut self, _sym: Self::Symbol) {
+        // All terminals are bytes.
+    }
+
+    fn start_sym(&self) -> NumericSymbol {
+        self.symbols.start_sym()
+    }
+
+    fn num_syms(&self) -> usize {
+        self.symbols.num_syms()
+    }
+}
+
+impl TerminalSymbolSet for ByteSymbols {
+    fn is_terminal(&self, sym: Self::Symbol) -> bool {
+        ByteSymbols::as_byte(sym).is_some()
+    }
+}
+
+impl NamedSymbols {
+    /// Creates a source of numeric symbols with an empty symbol space and no names.
+    pub fn new() -> Self {
+        NamedSymbols::default()
+    }
+
+    /// Binds a name to the symbol with the given ID. Generated symbols skip reserved IDs.
+    ///
+    /// IDs map to symbols one to one, so a reserved symbol's `usize()` equals its ID. The ID 0
+    /// can't be reserved, since numeric symbols are nonzero, and neither can 1, which belongs to
+    /// the start symbol. The first ID available for reservation is 2, which is also where
+    /// generated symbols begin. Systems that number symbols from 0 must offset their IDs by 2.
+    pub fn reserve_named(&mut self, name: &str, id: u32) -> Result<NumericSymbol, ReserveError> {
+        let error = |kind| Err(ReserveError { name: name.to_string(), id: id, kind: kind });
+        if id < FIRST_SYMBOL {
+            return error(ReserveErrorKind::InvalidId);
+        }
+        if self.names.contains_key(name) {
+            return error(ReserveErrorKind::NameTaken);
+        }
+        if id < self.symbols.next_sym || !self.reserved.insert(id) {
+            return error(ReserveErrorKind::IdTaken);
+        }
+        let sym = NumericSymbol::from(id as u64);
+        self.set_name(sym, name);
+        Ok(sym)
+    }
+
+    /// Returns the symbol bound to the given name, without allocating one.
+    pub fn lookup(&self, name: &str) -> Option<NumericSymbol> {
+        self.names.get(name).cloned()
+    }
+}
+
+impl SymbolSource for NamedSymbols {
+    type Symbol = NumericSymbol;
+
+    fn next_sym(&mut self, terminal: bool) -> NumericSymbol {
+        while self.reserved.contains(&self.symbols.next_sym) {
+            self.symbols.next_sym += 1;
+        }
+        self.symbols.next_sym(terminal)
+    }
+
+    fn mark_as_nonterminal(&mut self, _sym: Self::Symbol) {
+        // This information isn't stored.
+    }
+
+    fn start_sym(&self) -> NumericSymbol {
+        self.symbols.start_sym()
+    }
+
+    /// Includes reserved IDs.
+    fn num_syms(&self) -> usize {
+        let max_reserved = self.reserved.iter().next_back().map_or(0, |&id| id as usize + 1);
+        cmp::max(self.symbols.num_syms(), max_reserved)
+    }
+
+    fn has_names(&self) -> bool {
+        true
+    }
+
+    /// A name stays bound to the first symbol that was given it.
+    fn set_name(&mut self, sym: Self::Symbol, name: &str) {
+        self.names.entry(name.to_string()).or_insert(sym);
+        self.sym_names.insert(sym, name.to_string());
+    }
+
+    fn name_of(&self, sym: Self::Symbol) -> Option<&str> {
+        self.sym_names.get(&sym).map(|name| &name[..])
+    }
+
+    /// Returns the symbol bound to the name, if there is one. Otherwise, allocates a symbol and
+    /// binds the name to it.
+    fn sym_named(&mut self, name: &str) -> Self::Symbol {
+        match self.lookup(name) {
+            Some(sym) => sym,
+            None => {
+                let sym = self.next_sym(false);
+                self.set_name(sym, name);
+                sym
+            }
+        }
+    }
+}
+
 impl<S> Iterator for Terminals<S> where S: SymbolSource {
     type Item = S::Symbol;
 
@@ -156,6 +527,185 @@ impl<S> Iterator for Nonterminals<S> where S: SymbolSource {
     }
 }
 
+/// A set of symbols, stored as a bit vector indexed by symbol IDs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SymbolBitSet<S> {
+    bits: BitVec,
+    marker: PhantomData<S>,
+}
+
+/// Iterator over symbols in a `SymbolBitSet`.
+pub struct SymbolBitSetIter<'a, S> {
+    bits: iter::Enumerate<bit_vec::Iter<'a>>,
+    marker: PhantomData<S>,
+}
+
+impl<S> SymbolBitSet<S> where S: GrammarSymbol {
+    /// Creates an empty set for symbols with IDs smaller than `num_syms`.
+    pub fn new(num_syms: usize) -> Self {
+        SymbolBitSet::from_bit_vec(BitVec::from_elem(num_syms, false))
+    }
+
+    /// Creates a set from a bit vector indexed by symbol IDs.
+    pub fn from_bit_vec(bits: BitVec) -> Self {
+        SymbolBitSet {
+            bits: bits,
+            marker: PhantomData,
+        }
+    }
+
+    /// Checks whether the set contains a symbol.
+    pub fn contains(&self, sym: S) -> bool {
+        self.bits.get(sym.usize()).unwrap_or(false)
+    }
+
+    /// Adds a symbol to the set.
+    pub fn insert(&mut self, sym: S) {
+        if sym.usize() >= self.bits.len() {
+            let len = self.bits.len();
+            self.bits.grow(sym.usize() + 1 - len, false);
+        }
+        self.bits.set(sym.usize(), true);
+    }
+
+    /// Removes a symbol from the set.
+    pub fn remove(&mut self, sym: S) {
+        if sym.usize() < self.bits.len() {
+            self.bits.set(sym.usize(), false);
+        }
+    }
+
+    /// Returns the number of symbols in the set.
+    pub fn len(&self) -> usize {
+        self.bits.iter().filter(|&bit| bit).count()
+    }
+
+    /// Checks whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.bits.none()
+    }
+
+    /// Returns an iterator over symbols in the set, in the order of their IDs.
+    pub fn iter(&self) -> SymbolBitSetIter<S> {
+        SymbolBitSetIter {
+            bits: self.bits.iter().enumerate(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the bit vector indexed by symbol IDs.
+    pub fn bit_vec(&self) -> &BitVec {
+        &self.bits
+    }
+}
+
+impl<'a, S> Iterator for SymbolBitSetIter<'a, S> where S: GrammarSymbol {
+    type Item = S;
+
+    fn next(&mut self) -> Option<S> {
+        while let Some((id, is_present)) = self.bits.next() {
+            if is_present {
+                return Some(S::from(id as u64));
+            }
+        }
+        None
+    }
+}
+
+/// A mapping between old and new symbols of a grammar whose symbols were renumbered.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SymbolMap<S> where S: GrammarSymbol {
+    to_new: BTreeMap<S, S>,
+    to_old: BTreeMap<S, S>,
+}
+
+impl<S> SymbolMap<S> where S: GrammarSymbol {
+    /// Creates an empty mapping.
+    pub fn new() -> Self {
+        SymbolMap {
+            to_new: BTreeMap::new(),
+            to_old: BTreeMap::new(),
+        }
+    }
+
+    /// Maps the old symbol `old` to `new`.
+    pub fn insert(&mut self, old: S, new: S) {
+        self.to_new.insert(old, new);
+        self.to_old.insert(new, old);
+    }
+
+    /// Returns the new symbol for an old one, or `None` if the old symbol is not mapped.
+    pub fn to_new(&self, old: S) -> Option<S> {
+        self.to_new.get(&old).cloned()
+    }
+
+    /// Returns the old symbol for a new one, or `None` if the new symbol is not mapped.
+    pub fn to_old(&self, new: S) -> Option<S> {
+        self.to_old.get(&new).cloned()
+    }
+
+    /// Returns the number of mapped symbols.
+    pub fn len(&self) -> usize {
+        self.to_new.len()
+    }
+
+    /// Returns an iterator over pairs of old and new symbols, in the order of old symbols.
+    pub fn iter(&self) -> btree_map::Iter<S, S> {
+        self.to_new.iter()
+    }
+}
+
+impl SymbolMap<NumericSymbol> {
+    /// Maps the given symbols to consecutive IDs. The start symbol of `ConsecutiveSymbols`
+    /// keeps its ID, and other symbols keep their relative order. Returns the mapping and a
+    /// symbol source that continues after the last new symbol.
+    pub fn consecutive<I>(used: I) -> (Self, ConsecutiveSymbols) where
+                I: IntoIterator<Item=NumericSymbol> {
+        let mut used: BTreeSet<_> = used.into_iter().collect();
+        let mut map = SymbolMap::new();
+        let mut sym_source = ConsecutiveSymbols::new();
+        let start = sym_source.start_sym();
+        if used.remove(&start) {
+            map.insert(start, start);
+        }
+        for old in used {
+            map.insert(old, sym_source.next_sym(false));
+        }
+        (map, sym_source)
+    }
+}
+
+/// A table of values attached to terminal symbols, such as matchers for raw input.
+#[derive(Clone, Debug)]
+pub struct TerminalTable<S, T> {
+    values: Vec<Option<T>>,
+    marker: PhantomData<S>,
+}
+
+impl<S, T> TerminalTable<S, T> where S: GrammarSymbol {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        TerminalTable {
+            values: vec![],
+            marker: PhantomData,
+        }
+    }
+
+    /// Attaches a value to a terminal symbol, replacing the previous one.
+    pub fn set_terminal_value(&mut self, sym: S, value: T) {
+        let idx = sym.usize();
+        while self.values.len() <= idx {
+            self.values.push(None);
+        }
+        self.values[idx] = Some(value);
+    }
+
+    /// Returns the value attached to a terminal symbol.
+    pub fn terminal_value(&self, sym: S) -> Option<&T> {
+        self.values.get(sym.usize()).and_then(|value| value.as_ref())
+    }
+}
+
 /// Trait used to generate terminal symbols.
 pub trait SymbolContainer<S: GrammarSymbol> {
     /// Generates 
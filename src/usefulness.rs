@@ -3,11 +3,12 @@
 use bit_matrix::{FixedBitVec, FixedBitMatrix};
 use bit_vec::BitVec;
 
+use cycles::unit_derivation_matrix;
 use grammar::{ContextFree, ContextFreeRef, ContextFreeMut};
 use rhs_closure::RhsClosure;
 use rule::GrammarRule;
 use rule_container::RuleContainer;
-use symbol::{SymbolSource, GrammarSymbol};
+use symbol::{SymbolSource, GrammarSymbol, TerminalSymbolSet};
 
 /// Contains the information about usefulness of the grammar's rules.
 /// Useful rules are both reachable and productive.
@@ -35,6 +36,66 @@ pub struct UselessRule<R> {
     pub unproductive: bool,
 }
 
+/// A defect that makes the grammar unusable.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GrammarDefect<S> {
+    /// The start symbol doesn't derive any sentence.
+    UnproductiveStart(S),
+}
+
+/// A summary of the grammar's defects, with the affected symbols of each kind in the order of
+/// their IDs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HealthReport<S> {
+    /// Symbols that are used in rules, but can't be reached from the start symbol.
+    pub unreachable: Vec<S>,
+    /// Nonterminals that don't derive any sentence.
+    pub unproductive: Vec<S>,
+    /// Symbols that appear on the RHS of some rule, but neither have rules nor are classified as
+    /// terminals. These are listed only here, and not among unproductive symbols.
+    pub undefined: Vec<S>,
+    /// Nonterminals that derive themselves through a cycle of unit rules.
+    pub cyclic: Vec<S>,
+    /// Nonterminals that are both nullable and left-recursive.
+    pub nullable_left_recursive: Vec<S>,
+}
+
+impl<S> HealthReport<S> where S: GrammarSymbol {
+    /// Checks the grammar for all kinds of defects. Symbols without rules are productive only if
+    /// the grammar's symbol source classifies them as terminals.
+    pub fn new<'a, G>(grammar: &'a G, start: S) -> Self where
+                G: ContextFree<Symbol=S> + TerminalSymbolSet,
+                &'a G: ContextFreeRef<'a, Target=G> {
+        let num_syms = grammar.sym_source().num_syms();
+        let used = used_syms(grammar);
+        let reachable = reachable_from(grammar, &[start]);
+        let productive = productive_syms(grammar, |sym| grammar.is_terminal(sym));
+        let undefined = grammar.undefined_symbols();
+        let unit_derivation = unit_derivation_matrix(grammar);
+        let syms = |pred: &Fn(usize) -> bool| {
+            (0 .. num_syms).filter(|&id| pred(id)).map(|id| S::from(id as u64)).collect()
+        };
+        HealthReport {
+            unreachable: syms(&|id| used[id] && !reachable[id]),
+            unproductive: syms(&|id| {
+                used[id] && !productive[id] && !undefined.contains(S::from(id as u64))
+            }),
+            undefined: undefined.iter().collect(),
+            cyclic: syms(&|id| unit_derivation[(id, id)]),
+            nullable_left_recursive: grammar.nullable_left_recursion(),
+        }
+    }
+
+    /// Checks whether the report found no defects.
+    pub fn is_healthy(&self) -> bool {
+        self.unreachable.is_empty() &&
+            self.unproductive.is_empty() &&
+            self.undefined.is_empty() &&
+            self.cyclic.is_empty() &&
+            self.nullable_left_recursive.is_empty()
+    }
+}
+
 /// Returns the set of used symbols.
 fn used_syms<'a, G>(grammar: &'a G) -> FixedBitVec where
             G: ContextFree,
@@ -51,16 +112,28 @@ fn used_syms<'a, G>(grammar: &'a G) -> FixedBitVec where
     FixedBitVec::from_bit_vec(used_syms)
 }
 
-/// Returns the set of productive symbols.
-fn productive_syms<'a, G>(grammar: &'a G) -> FixedBitVec where
+/// Returns the set of productive symbols. Symbols that aren't on the LHS of any rule are
+/// productive if `is_terminal` says they're terminals.
+fn productive_syms<'a, G, F>(grammar: &'a G, is_terminal: F) -> FixedBitVec where
             G: ContextFree,
-            &'a G: ContextFreeRef<'a, Target=G> {
+            &'a G: ContextFreeRef<'a, Target=G>,
+            F: Fn(G::Symbol) -> bool {
     let num_syms = grammar.sym_source().num_syms();
-    let mut productive_syms = BitVec::from_elem(num_syms, true);
+    let mut nonterminal_syms = BitVec::from_elem(num_syms, false);
+
+    for rule in grammar.rules() {
+        nonterminal_syms.set(rule.lhs().usize(), true);
+    }
 
+    let mut productive_syms = BitVec::from_elem(num_syms, false);
+    for id in 0 .. num_syms {
+        if !nonterminal_syms[id] && is_terminal(G::Symbol::from(id as u64)) {
+            productive_syms.set(id, true);
+        }
+    }
     for rule in grammar.rules() {
-        if !rule.rhs().is_empty() {
-            productive_syms.set(rule.lhs().usize(), false);
+        if rule.rhs().is_empty() {
+            productive_syms.set(rule.lhs().usize(), true);
         }
     }
 
@@ -68,12 +141,18 @@ fn productive_syms<'a, G>(grammar: &'a G) -> FixedBitVec where
     FixedBitVec::from_bit_vec(productive_syms)
 }
 
-/// Returns the set of reachable symbols.
+/// Returns the set of symbols reachable from any of the start symbols.
 fn reachable_syms<'a, G>(grammar: &'a G) -> FixedBitVec where
             G: ContextFree,
             &'a G: ContextFreeRef<'a, Target=G> {
+    reachable_from(grammar, &grammar.start_symbols())
+}
+
+/// Returns the set of symbols reachable from any of the given symbols.
+fn reachable_from<'a, G>(grammar: &'a G, starts: &[G::Symbol]) -> FixedBitVec where
+            G: ContextFree,
+            &'a G: ContextFreeRef<'a, Target=G> {
     let num_syms = grammar.sym_source().num_syms();
-    let start_sym = grammar.start_sym().usize();
     let mut reachability = FixedBitMatrix::new(num_syms, num_syms);
 
     for rule in grammar.rules() {
@@ -85,11 +164,14 @@ fn reachable_syms<'a, G>(grammar: &'a G) -> FixedBitVec where
 
     reachability.transitive_closure();
 
-    let mut unreachable_syms = FixedBitVec::from_elem(num_syms, false);
-    for (syms, block) in unreachable_syms.iter_mut().zip(reachability[start_sym].iter()) {
-        *syms = *block;
+    let mut reachable_syms = FixedBitVec::from_elem(num_syms, false);
+    for &start_sym in starts {
+        let row = reachability[start_sym.usize()].iter();
+        for (syms, block) in reachable_syms.iter_mut().zip(row) {
+            *syms |= *block;
+        }
     }
-    unreachable_syms
+    reachable_syms
 }
 
 impl<'a, G> Usefulness<&'a mut G> where
@@ -99,7 +181,22 @@ impl<'a, G> Usefulness<&'a mut G> where
     /// Analyzes usefulness of the grammar's rules. In particular, it checks for reachable
     /// and productive symbols.
     pub fn new(grammar: &'a mut G) -> Usefulness<&'a mut G> {
-        let productivity = productive_syms(grammar);
+        let productivity = productive_syms(grammar, |_| true);
+        Usefulness::with_productivity(grammar, productivity)
+    }
+
+    /// Analyzes usefulness of the grammar's rules. Symbols without rules are productive only if
+    /// the grammar's symbol source classifies them as terminals.
+    pub fn with_terminal_set(grammar: &'a mut G) -> Usefulness<&'a mut G> where
+                G::Source: TerminalSymbolSet {
+        let productivity = {
+            let sym_source = grammar.sym_source();
+            productive_syms(grammar, |sym| sym_source.is_terminal(sym))
+        };
+        Usefulness::with_productivity(grammar, productivity)
+    }
+
+    fn with_productivity(grammar: &'a mut G, productivity: FixedBitVec) -> Usefulness<&'a mut G> {
         let reachability = reachable_syms(grammar);
         let used_syms = used_syms(grammar);
         let all_useful = productivity.storage().iter()
@@ -126,6 +223,17 @@ impl<'a, G> Usefulness<&'a mut G> where
     pub fn has_useless_rules(&self) -> bool {
         self.has_useless_rules
     }
+
+    /// Checks that every start symbol of the grammar is productive. Use `with_terminal_set` to
+    /// catch start symbols that refer only to undefined symbols.
+    pub fn validate(&self) -> Result<(), GrammarDefect<G::Symbol>> {
+        for start in self.grammar.start_symbols() {
+            if !self.productivity(start) {
+                return Err(GrammarDefect::UnproductiveStart(start));
+            }
+        }
+        Ok(())
+    }
 }
 
 // Watch out: Normal type bounds conflict with HRTB.
@@ -1,3 +1,6 @@
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 
 use cfg::*;
@@ -13,3 +16,50 @@ pub fn assert_eq_rules<R, I, J>(i: I, j: J) where
 
     assert_eq!(rules_i, rules_j);
 }
+
+/// Returns all sentences of at most `max_len` symbols derived from `start`. Symbols that never
+/// appear on a rule's LHS are terminals.
+pub fn language<R, I>(rules: I, start: R::Symbol, max_len: usize) -> BTreeSet<Vec<R::Symbol>> where
+            R: GrammarRule,
+            R::Symbol: GrammarSymbol,
+            I: Iterator<Item=R> {
+    let rules = rules.map(|rule| (rule.lhs(), rule.rhs().to_vec())).collect::<Vec<_>>();
+    let mut derived: BTreeMap<R::Symbol, BTreeSet<Vec<R::Symbol>>> = BTreeMap::new();
+    for &(lhs, _) in &rules {
+        derived.insert(lhs, BTreeSet::new());
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &(lhs, ref rhs) in &rules {
+            let mut prefixes = BTreeSet::new();
+            prefixes.insert(vec![]);
+            for sym in rhs {
+                let mut next = BTreeSet::new();
+                for prefix in &prefixes {
+                    if let Some(sentences) = derived.get(sym) {
+                        for sentence in sentences {
+                            if prefix.len() + sentence.len() <= max_len {
+                                let mut joined: Vec<R::Symbol> = prefix.clone();
+                                joined.extend(sentence.iter().cloned());
+                                next.insert(joined);
+                            }
+                        }
+                    } else if prefix.len() < max_len {
+                        let mut joined: Vec<R::Symbol> = prefix.clone();
+                        joined.push(*sym);
+                        next.insert(joined);
+                    }
+                }
+                prefixes = next;
+            }
+            let sentences = derived.get_mut(&lhs).unwrap();
+            let prev_cardinality = sentences.len();
+            sentences.extend(prefixes.into_iter());
+            changed |= sentences.len() != prev_cardinality;
+        }
+    }
+
+    derived.remove(&start).unwrap_or_else(|| BTreeSet::new())
+}
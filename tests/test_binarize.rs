@@ -3,6 +3,7 @@ extern crate cfg;
 mod support;
 
 use cfg::*;
+use cfg::history::{NullHistory, Traced};
 use cfg::usefulness::Usefulness;
 
 #[test]
@@ -79,3 +80,53 @@ fn test_binarize_very_long_rule() {
     equivalent.rule(start).rhs(long_rhs);
     support::assert_eq_rules(equivalent.rules(), cfg.rules());
 }
+
+#[test]
+fn test_rhs_length_histogram() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c) = cfg.sym();
+    cfg.rule(start).rhs([a, b, c, a, b])
+                   .rhs([a, b])
+                   .rhs([c])
+       .rule(c).rhs([]);
+    let histogram = cfg.rhs_length_histogram();
+    assert_eq!(histogram.into_iter().collect::<Vec<_>>(), vec![(0, 1), (1, 1), (2, 1), (5, 1)]);
+
+    let binarized = cfg.binarize();
+    let histogram = binarized.rhs_length_histogram();
+    assert!(histogram.keys().all(|&len| len <= 2));
+    assert_eq!(histogram.get(&2), Some(&5));
+}
+
+#[test]
+fn test_rule_ref_into_owned() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c) = cfg.sym();
+    cfg.rule(start).rhs([a, b, c]).rhs([b]);
+    let binarized = cfg.binarize();
+
+    let rules: Vec<Rule<_, _>> = binarized.rules().map(|rule| rule.into_owned()).collect();
+    assert_eq!(rules.len(), binarized.rules().count());
+    for (owned, rule) in rules.iter().zip(binarized.rules()) {
+        assert_eq!((owned.lhs(), owned.rhs()), (rule.lhs(), rule.rhs()));
+    }
+}
+
+#[test]
+fn test_original_rule() {
+    let mut cfg: Cfg<Traced<NullHistory>> = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c, d) = cfg.sym();
+    cfg.rule(start).rhs_with_history([a, b, c, d], Traced::new(7, NullHistory))
+                   .rhs_with_history([b], Traced::new(3, NullHistory));
+    let binarized = cfg.binarize();
+
+    assert_eq!(binarized.rules().count(), 4);
+    assert_eq!(binarized.original_rule(0), Some((7, 0)));
+    assert_eq!(binarized.original_rule(1), Some((7, 1)));
+    assert_eq!(binarized.original_rule(2), Some((7, 2)));
+    assert_eq!(binarized.original_rule(3), Some((3, 0)));
+    assert_eq!(binarized.original_rule(4), None);
+}
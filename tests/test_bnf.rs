@@ -0,0 +1,53 @@
+extern crate cfg;
+
+use cfg::*;
+use cfg::bnf::{parse_bnf, BnfError, BnfErrorKind};
+use cfg::history::NullHistory;
+
+#[test]
+fn test_parse_bnf() {
+    let grammar = "
+        // Lists of items.
+        %start list
+        item ::= a | b c; /* Items are short. */
+        list ::= item | list sep item /* left
+                                         recursive */ ;
+        opt ::= ε | item;
+    ";
+    let (cfg, syms): (Cfg<NullHistory>, _) = parse_bnf(grammar).unwrap();
+    let (list, item, opt) = (syms["list"], syms["item"], syms["opt"]);
+    let (a, b, c, sep) = (syms["a"], syms["b"], syms["c"], syms["sep"]);
+    assert_eq!(cfg.start_symbols(), vec![list]);
+    assert_eq!(cfg.start_sym(), list);
+
+    let rules = cfg.rules().map(|rule| (rule.lhs(), rule.rhs().to_vec())).collect::<Vec<_>>();
+    assert_eq!(rules, vec![
+        (item, vec![a]),
+        (item, vec![b, c]),
+        (list, vec![item]),
+        (list, vec![list, sep, item]),
+        (opt, vec![]),
+        (opt, vec![item]),
+    ]);
+}
+
+#[test]
+fn test_parse_bnf_default_start() {
+    let (cfg, syms): (Cfg<NullHistory>, _) = parse_bnf("expr ::= num; num ::= digit;").unwrap();
+    assert_eq!(cfg.start_symbols(), vec![syms["expr"]]);
+    assert_eq!(cfg.start_sym(), syms["expr"]);
+}
+
+#[test]
+fn test_parse_bnf_errors() {
+    let error = |grammar| parse_bnf::<NullHistory, NullHistory>(grammar).err().unwrap();
+    assert_eq!(error("a ::= b;\n  %token b"), BnfError {
+        line: 2,
+        column: 3,
+        kind: BnfErrorKind::UnknownDirective("token".to_string()),
+    });
+    assert_eq!(error("%start ;").kind, BnfErrorKind::MissingArgument);
+    assert_eq!(error("a ::= b").kind, BnfErrorKind::Unexpected(None));
+    assert_eq!(error("a b").kind, BnfErrorKind::Unexpected(Some("b".to_string())));
+    assert_eq!(error("a ::= /* b;").kind, BnfErrorKind::UnterminatedComment);
+}
@@ -0,0 +1,552 @@
+extern crate cfg;
+
+mod support;
+
+use std::collections::BTreeMap;
+use std::f64;
+use std::u64;
+
+use cfg::*;
+use cfg::history::{Action, NullHistory, RewriteSequence, Weighted};
+use cfg::recognizer::Recognizer;
+use cfg::rule_container::RuleContainer;
+use cfg::sequence::{Separator, Sequence};
+use cfg::symbol::{ByteSymbols, ConsecutiveSymbols, GrammarSymbol, NamedSymbols, NumericSymbol};
+use cfg::symbol::{ReserveError, ReserveErrorKind, TerminalSymbolSet};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Weight(f64);
+
+impl Action for Weight {
+    fn no_op(&self) -> Self { Weight(1.0) }
+}
+
+impl RewriteSequence for Weight {
+    type Rewritten = Weight;
+
+    fn sequence<H, S>(&self, _top: &Sequence<H, S>) -> Self where S: GrammarSymbol {
+        *self
+    }
+}
+
+#[test]
+fn test_merge_by_rhs() {
+    let mut cfg: Cfg<Weight> = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c) = cfg.sym();
+
+    cfg.rule(start).rhs_with_history([b, c], Weight(0.25))
+                   .rhs_with_history([a], Weight(0.5))
+                   .rhs_with_history([b, c], Weight(0.125))
+       .rule(a).rhs_with_history([b, c], Weight(1.0));
+    cfg.merge_by_rhs(|Weight(w1), Weight(w2)| Weight(w1 + w2));
+
+    let mut equivalent: Cfg<Weight> = Cfg::new();
+    let start = equivalent.start_sym();
+    let (a, b, c) = equivalent.sym();
+
+    // Order is significant.
+    equivalent.rule(start).rhs([b, c]).rhs([a])
+              .rule(a).rhs([b, c]);
+
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+    let weights = cfg.rules().map(|rule| *rule.history()).collect::<Vec<_>>();
+    assert_eq!(weights, &[Weight(0.375), Weight(0.5), Weight(1.0)]);
+}
+
+#[test]
+fn test_normalize_probabilities() {
+    let mut cfg: Cfg<Weighted<NullHistory>> = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, single, zero) = cfg.sym();
+    cfg.rule(start).rhs_with_history([a], Weighted::new(3.0, NullHistory))
+                   .rhs_with_history([b], Weighted::new(1.0, NullHistory))
+                   .rhs_with_history([single, zero], Weighted::new(4.0, NullHistory));
+    cfg.rule(single).rhs_with_history([a, b], Weighted::new(5.0, NullHistory));
+    cfg.rule(zero).rhs_with_history([a], Weighted::new(0.0, NullHistory))
+                  .rhs_with_history([b], Weighted::new(0.0, NullHistory));
+    cfg.normalize_probabilities();
+
+    let weights = cfg.rules().map(|rule| rule.history().weight).collect::<Vec<_>>();
+    assert_eq!(weights, vec![0.375, 0.125, 0.5, 1.0, 0.5, 0.5]);
+    for &lhs in &[start, single, zero] {
+        let sum = cfg.rules().filter(|rule| rule.lhs() == lhs)
+                             .map(|rule| rule.history().weight)
+                             .fold(0.0, |sum, weight| sum + weight);
+        assert!((sum - 1.0).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_byte_cfg() {
+    let mut cfg: ByteCfg = Cfg::over_bytes();
+    let start = cfg.start_sym();
+    let digits = cfg.next_sym(false);
+    cfg.add_literal(start, b"0x", NullHistory);
+    cfg.rule(start).rhs([ByteSymbols::byte(b'0'), ByteSymbols::byte(b'x'), digits]);
+    cfg.rule(digits).rhs([ByteSymbols::byte(b'1')])
+                    .rhs([digits, ByteSymbols::byte(b'1')]);
+
+    assert!(cfg.is_terminal(ByteSymbols::byte(b'x')));
+    assert!(!cfg.is_terminal(digits));
+    assert_eq!(ByteSymbols::as_byte(ByteSymbols::byte(b'x')), Some(b'x'));
+    assert_eq!(ByteSymbols::as_byte(digits), None);
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(start, &ByteSymbols::literal(b"0x")));
+    assert!(recognizer.recognize(start, &ByteSymbols::literal(b"0x111")));
+    assert!(!recognizer.recognize(start, &ByteSymbols::literal(b"0x2")));
+}
+
+#[test]
+fn test_remove_rules_where_history() {
+    let mut cfg: Cfg<Weighted<NullHistory>> = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c) = cfg.sym();
+    cfg.rule(start).rhs_with_history([a], Weighted::new(0.6, NullHistory))
+                   .rhs_with_history([b], Weighted::new(0.05, NullHistory))
+                   .rhs_with_history([c], Weighted::new(0.35, NullHistory));
+    cfg.rule(a).rhs_with_history([b, c], Weighted::new(0.01, NullHistory));
+    cfg.remove_rules_where_history(|history| history.weight < 0.1);
+
+    let rhss = cfg.rules().map(|rule| rule.rhs().to_vec()).collect::<Vec<_>>();
+    assert_eq!(rhss, vec![vec![a], vec![c]]);
+}
+
+#[test]
+fn test_write_bnf_dot() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, a, sep) = cfg.sym();
+    cfg.rule(start).rhs([list])
+                   .rhs([]);
+    cfg.sequence(list).intersperse(sep).rhs(a, 1..);
+    cfg.rewrite_sequences();
+
+    let mut bnf = String::new();
+    cfg.write_bnf(&mut bnf).unwrap();
+    assert_eq!(bnf, "s1 ::= s2 | ε;\ns2 ::= s3 | s2 s4 s3;\n");
+
+    let mut dot = String::new();
+    cfg.write_dot(&mut dot).unwrap();
+    let mut dot_again = String::new();
+    cfg.write_dot(&mut dot_again).unwrap();
+    assert_eq!(dot, dot_again);
+    assert_eq!(dot, "digraph {\n    s1;\n    s2;\n    s3;\n    s4;\n    \
+                               s1 -> s2;\n    s2 -> s2;\n    s2 -> s3;\n    s2 -> s4;\n}\n");
+}
+
+#[test]
+fn test_grammar_metadata() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let a = cfg.sym();
+    cfg.rule(start).rhs([a]);
+    cfg.set_grammar_name("letters");
+    cfg.set_metadata("version", "2");
+    assert_eq!(cfg.set_metadata("author", "nobody"), None);
+    assert_eq!(cfg.set_metadata("author", "somebody"), Some("nobody".to_string()));
+
+    let copy = cfg.clone();
+    assert_eq!(copy.grammar_name(), Some("letters"));
+    assert_eq!(copy.metadata().get("author").map(|value| &value[..]), Some("somebody"));
+
+    let mut bnf = String::new();
+    cfg.write_bnf(&mut bnf).unwrap();
+    assert_eq!(bnf, "// letters\n// author: somebody\n// version: 2\ns1 ::= s2;\n");
+}
+
+#[test]
+fn test_max_rhs() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c, d, e) = cfg.sym();
+    cfg.set_max_rhs(2);
+    cfg.rule(start).rhs([a, b, c, d, e])
+                   .rhs([a, b]);
+
+    assert!(cfg.rules().all(|rule| rule.rhs().len() <= 2));
+    assert_eq!(cfg.rules().count(), 5);
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(start, &[a, b, c, d, e]));
+    assert!(recognizer.recognize(start, &[a, b]));
+    assert!(!recognizer.recognize(start, &[a, b, c]));
+}
+
+#[test]
+fn test_rule_container_without_action() {
+    // `u32` doesn't implement `Action`.
+    let mut cfg: Cfg<u32, u32> = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b) = cfg.sym();
+    cfg.add_rule(start, &[a, b], 1);
+    cfg.add_rule(start, &[], 2);
+    cfg.retain(|_, _, &history| history != 2);
+    let augmented = cfg.augment_start();
+    assert_eq!(cfg.start_sym(), augmented);
+}
+
+#[test]
+fn test_compacted_clone() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (_unused, a, _unused2, b) = cfg.sym();
+    cfg.rule(start).rhs([a, b])
+                   .rhs([]);
+    cfg.rule(a).rhs([b]);
+
+    let (compacted, map) = cfg.compacted_clone();
+
+    let rules = |cfg: &Cfg| {
+        cfg.rules().map(|rule| (rule.lhs(), rule.rhs().to_vec())).collect::<Vec<_>>()
+    };
+    assert_eq!(rules(&cfg), vec![(start, vec![a, b]), (start, vec![]), (a, vec![b])]);
+    assert_eq!(cfg.sym_source().num_syms(), 6);
+
+    let (new_a, new_b) = (map.to_new(a).unwrap(), map.to_new(b).unwrap());
+    assert_eq!(map.to_new(start), Some(start));
+    assert_eq!((new_a.usize(), new_b.usize()), (2, 3));
+    assert_eq!(map.to_old(new_b), Some(b));
+    assert_eq!(map.to_new(_unused), None);
+    assert_eq!(map.len(), 3);
+    assert_eq!(rules(&compacted),
+               vec![(start, vec![new_a, new_b]), (start, vec![]), (new_a, vec![new_b])]);
+    assert_eq!(compacted.sym_source().num_syms(), 4);
+}
+
+#[test]
+fn test_derivations() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (expr, plus, num, lparen, rparen) = cfg.sym();
+    cfg.rule(start).rhs([expr]);
+    cfg.rule(expr).rhs([lparen, expr, rparen])
+                  .rhs([expr, plus, expr])
+                  .rhs([num]);
+
+    let sentences = cfg.derivations(start).take(10).collect::<Vec<_>>();
+    assert_eq!(sentences.len(), 10);
+    assert_eq!(sentences[0], vec![num]);
+    assert_eq!(sentences[1], vec![lparen, num, rparen]);
+    for pair in sentences.windows(2) {
+        assert!(pair[0].len() <= pair[1].len());
+    }
+    let recognizer = Recognizer::new(&cfg);
+    for sentence in &sentences {
+        assert!(recognizer.recognize(start, sentence));
+    }
+}
+
+#[test]
+fn test_reverse() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, a, b, c, sep) = cfg.sym();
+    cfg.rule(start).rhs([a, b, c])
+                   .rhs([list, c]);
+    cfg.sequence(list).separator(Separator::Trailing(sep)).rhs(a, 1..);
+
+    let reversed = cfg.reverse();
+    let rhss = reversed.rules().take(2).map(|rule| rule.rhs().to_vec()).collect::<Vec<_>>();
+    assert_eq!(rhss, vec![vec![c, b, a], vec![c, list]]);
+
+    let recognizer = Recognizer::new(&reversed);
+    assert!(recognizer.recognize(start, &[c, b, a]));
+    assert!(!recognizer.recognize(start, &[a, b, c]));
+    assert!(recognizer.recognize(start, &[c, sep, a, sep, a]));
+    assert!(!recognizer.recognize(start, &[c, a, sep]));
+}
+
+#[test]
+fn test_write_lark() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (args, arg, comma, lparen, rparen) = cfg.sym();
+    cfg.rule(start).rhs([lparen, args, rparen]);
+    cfg.sequence(args).intersperse(comma).rhs(arg, 0..);
+    let mut names = BTreeMap::new();
+    names.insert("call".to_string(), start);
+    names.insert("args".to_string(), args);
+    names.insert("comma".to_string(), comma);
+
+    let mut lark = String::new();
+    cfg.write_lark(&names, &mut lark).unwrap();
+    assert_eq!(lark, "call: S5 args S6\n\
+                      args: (S3 (COMMA S3)*)?\n\
+                      %declare S3 COMMA S5 S6\n");
+
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, a, b) = cfg.sym();
+    cfg.rule(start).rhs([list, a, b]);
+    cfg.rule(list).rhs([]);
+    let mut names = BTreeMap::new();
+    names.insert("_start".to_string(), start);
+    names.insert("item{1,}".to_string(), list);
+    names.insert("comma".to_string(), a);
+    names.insert("COMMA".to_string(), b);
+
+    let mut lark = String::new();
+    cfg.write_lark(&names, &mut lark).unwrap();
+    assert_eq!(lark, "s_start: item_1__ COMMA COMMA_2\n\
+                      item_1__: \n\
+                      %declare COMMA COMMA_2\n");
+
+    // Sequences of zero items are empty alternatives.
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (empty, list, a, b) = cfg.sym();
+    cfg.rule(start).rhs([empty, list]);
+    cfg.sequence(empty).rhs(a, 0..1);
+    cfg.sequence(list).rhs(a, 0..1);
+    cfg.rule(list).rhs([b])
+                  .rhs([a, b]);
+    let mut lark = String::new();
+    cfg.write_lark(&BTreeMap::new(), &mut lark).unwrap();
+    assert_eq!(lark, "s1: s2 s3\n\
+                      s2: \n\
+                      s3: (S5 | S4 S5)?\n\
+                      %declare S4 S5\n");
+}
+
+#[test]
+fn test_nullable_left_recursion() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, opt, indirect, left, a) = cfg.sym();
+    cfg.rule(start).rhs([list, indirect, left]);
+    // list ::= ε | list a
+    cfg.rule(list).rhs([])
+                  .rhs([list, a]);
+    // indirect ::= opt indirect a | ε, with a nullable opt before the recursion.
+    cfg.rule(opt).rhs([])
+                 .rhs([a]);
+    cfg.rule(indirect).rhs([opt, indirect, a])
+                      .rhs([]);
+    // left ::= a | left a is left-recursive, but not nullable.
+    cfg.rule(left).rhs([a])
+                  .rhs([left, a]);
+
+    assert_eq!(cfg.nullable_left_recursion(), vec![list, indirect]);
+}
+
+#[test]
+fn test_graft() {
+    let mut number: Cfg = Cfg::new();
+    let num_start = number.start_sym();
+    let (digit, minus) = number.sym();
+    number.rule(num_start).rhs([minus, num_start]);
+    number.sequence(num_start).rhs(digit, 1..);
+
+    let mut expr: Cfg = Cfg::new();
+    let start = expr.start_sym();
+    let (placeholder, plus) = expr.sym();
+    expr.rule(start).rhs([placeholder])
+                    .rhs([start, plus, placeholder]);
+
+    let map = expr.graft(placeholder, &number, num_start);
+    assert_eq!(map.to_new(num_start), Some(placeholder));
+    let (digit, minus) = (map.to_new(digit).unwrap(), map.to_new(minus).unwrap());
+    assert!(digit != plus && minus != plus);
+    expr.rewrite_sequences();
+
+    let recognizer = Recognizer::new(&expr);
+    assert!(recognizer.recognize(start, &[digit, digit, plus, minus, digit]));
+    assert!(!recognizer.recognize(start, &[plus, digit]));
+    assert!(!recognizer.recognize(start, &[digit, minus]));
+}
+
+#[test]
+fn test_right_factor() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c, d, e) = cfg.sym();
+    cfg.rule(start).rhs([a])
+                   .rhs([e]);
+    cfg.rule(a).rhs([b, c])
+               .rhs([e, d])
+               .rhs([d, c]);
+    let original = cfg.clone();
+    cfg.right_factor();
+
+    let mut equivalent: Cfg = Cfg::new();
+    let start = equivalent.start_sym();
+    let (a, b, c, d, e, helper) = equivalent.sym();
+
+    // Order is significant.
+    equivalent.rule(start).rhs([a])
+                          .rhs([e]);
+    equivalent.rule(a).rhs([helper, c])
+                      .rhs([e, d]);
+    equivalent.rule(helper).rhs([b])
+                           .rhs([d]);
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+
+    let original = Recognizer::new(&original);
+    let factored = Recognizer::new(&cfg);
+    for input in &[vec![b, c], vec![d, c], vec![e, d], vec![e], vec![b, d], vec![c]] {
+        assert_eq!(original.recognize(start, input), factored.recognize(start, input));
+    }
+}
+
+#[test]
+fn test_write_peg() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c) = cfg.sym();
+    cfg.rule(a).rhs([c])
+               .rhs([b]);
+    cfg.rule(start).rhs([a, b])
+                   .rhs([]);
+
+    let mut peg = String::new();
+    cfg.write_peg(&mut peg).unwrap();
+    assert_eq!(peg, "s1 <- s2 s3 / \"\"\ns2 <- s4 / s3\n");
+}
+
+#[test]
+fn test_default() {
+    #[derive(Default)]
+    struct Embedded {
+        grammar: Cfg,
+    }
+
+    let mut embedded = Embedded::default();
+    assert_eq!(embedded.grammar.rules().count(), 0);
+    let (a, b) = embedded.grammar.sym();
+    let mut fresh: Cfg = Cfg::new();
+    assert_eq!((a, b), fresh.sym());
+
+    let mut sym_source = ConsecutiveSymbols::default();
+    let sym: NumericSymbol = sym_source.sym();
+    assert_eq!(sym, a);
+    let bytes: ByteCfg = Default::default();
+    assert_eq!(bytes.sym_source().num_syms(), ByteSymbols::new().num_syms());
+}
+
+#[test]
+fn test_sentence_counts_up_to() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, elem, a, b) = cfg.sym();
+    cfg.sequence(list).inclusive(1, Some(3)).rhs_with_history(elem, NullHistory);
+    // Strings of a and b of any length.
+    cfg.rule(start).rhs([])
+                   .rhs([a, start])
+                   .rhs([b, start]);
+    cfg.rewrite_sequences();
+
+    assert_eq!(cfg.sentence_counts_up_to(list, 4), vec![0, 1, 1, 1, 0]);
+    assert_eq!(cfg.sentence_counts_up_to(start, 4), vec![1, 2, 4, 8, 16]);
+
+    // A cycle gives infinitely many derivations.
+    cfg.rule(start).rhs([start]);
+    assert_eq!(cfg.sentence_counts_up_to(start, 1), vec![u64::MAX, u64::MAX]);
+}
+
+#[test]
+fn test_reserve_named() {
+    let mut sym_source = NamedSymbols::new();
+    // Numeric symbols are nonzero, and 1 is the start symbol, so 2 is the lowest ID for EOF.
+    let eof = sym_source.reserve_named("EOF", 2).unwrap();
+    assert_eq!(eof.usize(), 2);
+    let semi = sym_source.reserve_named("SEMI", 4).unwrap();
+    let (a, b, c): (NumericSymbol, NumericSymbol, NumericSymbol) = sym_source.sym();
+    assert_eq!((a.usize(), b.usize(), c.usize()), (3, 5, 6));
+    assert_eq!(sym_source.lookup("EOF"), Some(eof));
+    assert_eq!(sym_source.lookup("SEMI"), Some(semi));
+    assert_eq!(sym_source.lookup("x"), None);
+    assert_eq!(sym_source.sym_named("EOF"), eof);
+    assert_eq!(sym_source.name_of(semi), Some("SEMI"));
+
+    let error = |id, kind| Err(ReserveError { name: "x".to_string(), id: id, kind: kind });
+    assert_eq!(sym_source.reserve_named("x", 2), error(2, ReserveErrorKind::IdTaken));
+    assert_eq!(sym_source.reserve_named("x", 5), error(5, ReserveErrorKind::IdTaken));
+    assert_eq!(sym_source.reserve_named("x", 0), error(0, ReserveErrorKind::InvalidId));
+    assert_eq!(sym_source.reserve_named("x", 1), error(1, ReserveErrorKind::InvalidId));
+    assert_eq!(sym_source.reserve_named("EOF", 10),
+               Err(ReserveError { name: "EOF".to_string(), id: 10,
+                                  kind: ReserveErrorKind::NameTaken }));
+
+    let mut cfg: Cfg<NullHistory, NullHistory, NamedSymbols> = Cfg::with_sym_source(sym_source);
+    let start = cfg.start_sym();
+    let d = cfg.sym::<NumericSymbol>();
+    cfg.rule(start).rhs([d, eof]);
+    assert_eq!(d.usize(), 7);
+}
+
+#[test]
+fn test_set_all_histories() {
+    let mut cfg: Cfg<Weight> = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b) = cfg.sym();
+    cfg.rule(start).rhs_with_history([a, b], Weight(0.25))
+                   .rhs_with_history([b], Weight(0.75))
+       .rule(a).rhs_with_history([], Weight(1.0));
+    cfg.set_all_histories(Weight(0.5));
+
+    assert_eq!(cfg.rules().count(), 3);
+    assert!(cfg.rules().all(|rule| *rule.history() == Weight(0.5)));
+}
+
+#[test]
+fn test_ambiguity_rate() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, elem, sep) = cfg.sym();
+    cfg.rule(start).rhs([list]);
+    cfg.sequence(list).intersperse(sep).rhs(elem, 1..);
+    cfg.rewrite_sequences();
+    assert_eq!(cfg.ambiguity_rate(start, 5), 0.0);
+
+    // start ::= start start | elem
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let elem = cfg.sym();
+    cfg.rule(start).rhs([start, start]).rhs([elem]);
+    // Only `elem` and `elem elem` are unambiguous.
+    assert_eq!(cfg.ambiguity_rate(start, 4), 0.5);
+}
+
+#[test]
+fn test_ambiguity_rate_cycle() {
+    // start ::= start | elem
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let elem = cfg.sym();
+    cfg.rule(start).rhs([start]).rhs([elem]);
+    assert_eq!(cfg.ambiguity_rate(start, 3), f64::INFINITY);
+}
+
+#[test]
+fn test_epsilon_free_clone() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c, opt, unused) = cfg.sym();
+    cfg.rule(start).rhs([a, opt, b]).rhs([opt, opt])
+       .rule(opt).rhs([]).rhs([c])
+       .rule(unused).rhs([]);
+
+    let clone = cfg.epsilon_free_clone(start);
+    assert!(clone.rules().all(|rule| !rule.rhs().is_empty() || rule.lhs() == start));
+    assert!(clone.rules().any(|rule| rule.rhs().is_empty()));
+    assert!(clone.rules().all(|rule| rule.lhs() != unused));
+
+    let original = Recognizer::new(&cfg);
+    let recognizer = Recognizer::new(&clone);
+    // All nonempty strings of up to 3 symbols.
+    let mut inputs: Vec<Vec<NumericSymbol>> = vec![vec![]];
+    for _ in 0 .. 3 {
+        let mut longer = vec![];
+        for input in &inputs {
+            for &sym in &[a, b, c] {
+                let mut input = input.clone();
+                input.push(sym);
+                assert_eq!(recognizer.recognize(start, &input),
+                           original.recognize(start, &input));
+                longer.push(input);
+            }
+        }
+        inputs = longer;
+    }
+}
@@ -0,0 +1,53 @@
+extern crate cfg;
+
+use cfg::*;
+use cfg::sequence::Recursion;
+
+#[test]
+fn test_star() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b) = cfg.sym();
+    cfg.sequence(start).rhs(a, 0..);
+    cfg.rewrite_sequences();
+
+    let dfa = cfg.to_dfa(start).unwrap();
+    assert_eq!(dfa.num_states(), 1);
+    assert!(dfa.accepts(&[]));
+    assert!(dfa.accepts(&[a, a, a]));
+    assert!(!dfa.accepts(&[a, b]));
+}
+
+#[test]
+fn test_right_linear() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, elem, sep) = cfg.sym();
+    // Comma-separated lists, with right recursion.
+    cfg.rule(start).rhs([list]);
+    cfg.sequence(list).intersperse(sep).recursion(Recursion::Right).rhs(elem, 1..);
+    cfg.rewrite_sequences();
+
+    let dfa = cfg.to_dfa(start).unwrap();
+    assert_eq!(dfa.num_states(), 2);
+    assert!(dfa.accepts(&[elem]));
+    assert!(dfa.accepts(&[elem, sep, elem]));
+    assert!(!dfa.accepts(&[elem, sep]));
+    assert!(!dfa.accepts(&[]));
+}
+
+#[test]
+fn test_not_regular() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c) = cfg.sym();
+    cfg.rule(start).rhs([a, start, b]).rhs([]);
+    assert!(cfg.to_dfa(start).is_none());
+
+    // Unproductive symbols are rejected.
+    cfg.rule(c).rhs([c, a]);
+    let dfa = cfg.to_dfa(c).unwrap();
+    assert_eq!(dfa.num_states(), 1);
+    assert!(!dfa.accepts(&[]));
+    assert!(!dfa.accepts(&[a]));
+}
@@ -0,0 +1,20 @@
+#![cfg(feature = "petgraph")]
+
+extern crate cfg;
+
+use cfg::*;
+
+#[test]
+fn test_to_petgraph() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (expr, a, b) = cfg.sym();
+    cfg.rule(start).rhs([expr, a, expr]);
+    cfg.rule(expr).rhs([b]);
+
+    let (graph, nodes) = cfg.to_petgraph();
+    assert_eq!(graph.node_count(), 4);
+    assert_eq!(graph.edge_count(), 1);
+    assert_eq!(graph[nodes[&expr]], expr);
+    assert!(graph.find_edge(nodes[&start], nodes[&expr]).is_some());
+}
@@ -0,0 +1,176 @@
+extern crate cfg;
+
+use std::collections::BTreeSet;
+
+use cfg::*;
+use cfg::history::NullHistory;
+use cfg::prediction::{AnalysisDelta, FirstSets, FollowSets, IncrementalFirstSets, Ll1Error};
+use cfg::prediction::Ll1Table;
+use cfg::symbol::{ClassifiedSymbols, NumericSymbol};
+
+#[test]
+fn test_write_ll1_table() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, b) = cfg.sym();
+    cfg.rule(start).rhs([a, start, b])
+                   .rhs([]);
+
+    let table = Ll1Table::new(&cfg, start).unwrap();
+    assert_eq!(table.get(start, Some(a)), Some(0));
+    assert_eq!(table.get(start, Some(b)), Some(1));
+    assert_eq!(table.get(start, None), Some(1));
+
+    let mut grid = String::new();
+    cfg.write_ll1_table(start, &mut grid).unwrap();
+    assert_eq!(grid, "  2 3 $\n1 0 1 1\n");
+}
+
+#[test]
+fn test_ll1_conflict() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, b) = cfg.sym();
+    cfg.rule(start).rhs([a])
+                   .rhs([a, b]);
+
+    let mut grid = String::new();
+    assert_eq!(cfg.write_ll1_table(start, &mut grid),
+               Err(Ll1Error::Conflict(start, Some(a), 0, 1)));
+}
+
+#[test]
+fn test_rules_by_first_terminal() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (t, u) = cfg.sym();
+    let optional = cfg.next_sym(false);
+    cfg.rule(start).rhs([t, u])
+                   .rhs([optional, t])
+                   .rhs([u, t]);
+    cfg.rule(optional).rhs([])
+                      .rhs([u]);
+
+    assert_eq!(cfg.rules_starting_with(t), vec![0]);
+    assert_eq!(cfg.rules_with_t_in_first(t), vec![0, 1]);
+    assert_eq!(cfg.rules_with_t_in_first(u), vec![1, 2, 4]);
+}
+
+#[test]
+fn test_first_of() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, b, c) = cfg.sym();
+    let (opt, list, rec) = (cfg.next_sym(false), cfg.next_sym(false), cfg.next_sym(false));
+    cfg.rule(start).rhs([opt, list, c])
+                   .rhs([rec]);
+    cfg.rule(opt).rhs([])
+                 .rhs([a]);
+    cfg.rule(list).rhs([])
+                  .rhs([list, b]);
+    // A cycle that derives nothing.
+    cfg.rule(rec).rhs([rec]);
+
+    let first_sets = FirstSets::new(&cfg);
+    for &sym in &[start, opt, list, rec] {
+        let first = FirstSets::first_of(&cfg, sym);
+        assert_eq!(Some(&first), first_sets.first_sets().get(&sym));
+    }
+    assert_eq!(FirstSets::first_of(&cfg, start).into_iter().collect::<Vec<_>>(),
+               vec![Some(a), Some(b), Some(c)]);
+    assert_eq!(FirstSets::first_of(&cfg, b).into_iter().collect::<Vec<_>>(), vec![Some(b)]);
+}
+
+#[test]
+fn test_predict_sets() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, b, opt) = cfg.sym();
+    cfg.rule(start).rhs([opt, b])
+       .rule(opt).rhs([a])
+                 .rhs([]);
+
+    let sets = |syms: &[Option<NumericSymbol>]| syms.iter().cloned().collect::<BTreeSet<_>>();
+    assert_eq!(cfg.predict_sets(start), vec![sets(&[Some(a), Some(b)]),
+                                             sets(&[Some(a)]),
+                                             sets(&[Some(b)])]);
+}
+
+#[test]
+fn test_incremental_first_sets() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, b) = cfg.sym();
+    let (x, y) = (cfg.next_sym(false), cfg.next_sym(false));
+    cfg.rule(start).rhs([x, y])
+       .rule(x).rhs([a])
+       .rule(y).rhs([b]);
+
+    let mut analysis = IncrementalFirstSets::new(cfg);
+    assert!(!analysis.is_nullable(x));
+    let delta = analysis.add_rule_incremental(x, &[], NullHistory);
+    assert_eq!(delta, AnalysisDelta { nullable: vec![x], first: vec![start, x] });
+    assert!(analysis.is_nullable(x));
+    assert!(!analysis.is_nullable(start));
+    assert_eq!(analysis.first_sets(), FirstSets::new(analysis.grammar()).first_sets());
+
+    let delta = analysis.add_rule_incremental(y, &[x], NullHistory);
+    assert_eq!(delta, AnalysisDelta { nullable: vec![start, y], first: vec![start, y] });
+    assert_eq!(analysis.first_sets(), FirstSets::new(analysis.grammar()).first_sets());
+
+    let delta = analysis.add_rule_incremental(y, &[a, b], NullHistory);
+    assert_eq!(delta, AnalysisDelta { nullable: vec![], first: vec![] });
+
+    // A terminal that gets a rule becomes a nonterminal.
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, z) = cfg.sym();
+    cfg.rule(start).rhs([z]);
+    let mut analysis = IncrementalFirstSets::new(cfg);
+    let delta = analysis.add_rule_incremental(z, &[a], NullHistory);
+    assert_eq!(delta, AnalysisDelta { nullable: vec![], first: vec![start, z] });
+    assert_eq!(analysis.first_sets(), FirstSets::new(analysis.grammar()).first_sets());
+}
+
+#[test]
+fn test_follow_sets_set_start() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let (a, b) = cfg.sym();
+    let expr = cfg.next_sym(false);
+    cfg.rule(expr).rhs([a, expr, b])
+                  .rhs([a]);
+    cfg.set_start(expr);
+    assert_eq!(cfg.start_sym(), expr);
+    assert_eq!(cfg.binarize().start_sym(), expr);
+
+    let first_sets = FirstSets::new(&cfg);
+    let follow_sets = FollowSets::new(&cfg, &first_sets);
+    let expected: BTreeSet<_> = vec![None, Some(b)].into_iter().collect();
+    assert_eq!(follow_sets.follow_sets()[&expr], expected);
+}
+
+#[test]
+fn test_follow_sets_augment_start() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let a = cfg.sym();
+    cfg.rule(start).rhs([a, start])
+                   .rhs([a]);
+    let augmented = cfg.augment_start();
+    assert_eq!(cfg.start_sym(), augmented);
+
+    let first_sets = FirstSets::new(&cfg);
+    let follow_sets = FollowSets::new(&cfg, &first_sets);
+    let end: BTreeSet<_> = vec![None].into_iter().collect();
+    assert_eq!(follow_sets.follow_sets()[&augmented], end);
+    assert_eq!(follow_sets.follow_sets()[&start], end);
+}
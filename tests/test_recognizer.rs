@@ -0,0 +1,142 @@
+extern crate cfg;
+
+use cfg::*;
+use cfg::recognizer::Recognizer;
+use cfg::sequence::Separator::*;
+use cfg::symbol::TerminalTable;
+
+#[test]
+fn test_recognize() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, nullable) = cfg.sym();
+
+    cfg.rule(start).rhs([nullable, a, nullable, b])
+       .rule(nullable).rhs([]).rhs([nullable, nullable]).rhs([a]);
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(start, &[a, b]));
+    assert!(recognizer.recognize(start, &[a, a, a, b]));
+    assert!(!recognizer.recognize(start, &[a]));
+    assert!(!recognizer.recognize(start, &[b, a]));
+}
+
+#[test]
+fn test_leftmost_derivation() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (elem, sep) = cfg.sym();
+
+    cfg.sequence(start).separator(Proper(sep)).rhs(elem, 1..);
+    cfg.rewrite_sequences();
+
+    // 0: start ::= elem
+    // 1: start ::= start sep elem
+    let recognizer = Recognizer::new(&cfg);
+    assert_eq!(recognizer.leftmost_derivation(start, &[elem, sep, elem]), Some(vec![1, 0]));
+    assert_eq!(recognizer.leftmost_derivation(start, &[elem]), Some(vec![0]));
+    assert_eq!(recognizer.leftmost_derivation(start, &[elem, elem]), None);
+}
+
+#[test]
+fn test_leftmost_derivation_with_cycle() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, x) = cfg.sym();
+
+    cfg.rule(start).rhs([a, b])
+       .rule(a).rhs([b]).rhs([x])
+       .rule(b).rhs([a]).rhs([]);
+
+    let recognizer = Recognizer::new(&cfg);
+    assert_eq!(recognizer.leftmost_derivation(start, &[x]), Some(vec![0, 2, 4]));
+}
+
+#[test]
+fn test_valid_next() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (items, item, open, close, a, b, c, sep) = cfg.sym();
+    cfg.rule(start).rhs([open, items, close]);
+    cfg.rule(item).rhs([a])
+                  .rhs([b, c]);
+    cfg.sequence(items).intersperse(sep).rhs(item, 0..);
+    cfg.rewrite_sequences();
+
+    let recognizer = Recognizer::new(&cfg);
+    let valid_next = |prefix: &[_]| recognizer.valid_next(start, prefix).iter().collect::<Vec<_>>();
+    assert_eq!(valid_next(&[]), vec![open]);
+    assert_eq!(valid_next(&[open]), vec![close, a, b]);
+    assert_eq!(valid_next(&[open, b]), vec![c]);
+    assert_eq!(valid_next(&[open, a]), vec![close, sep]);
+    assert_eq!(valid_next(&[open, a, sep]), vec![a, b]);
+    assert_eq!(valid_next(&[open, a, close]), vec![]);
+    assert_eq!(valid_next(&[close]), vec![]);
+}
+
+#[test]
+fn test_recognize_with_table() {
+    fn is_digit(ch: &char) -> bool { ch.is_digit(10) }
+    fn is_plus(ch: &char) -> bool { *ch == '+' }
+
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (number, digit, plus) = cfg.sym();
+    cfg.rule(start).rhs([number])
+                   .rhs([start, plus, number]);
+    cfg.sequence(number).rhs(digit, 1..);
+    cfg.rewrite_sequences();
+
+    let mut table = TerminalTable::new();
+    table.set_terminal_value(digit, is_digit as fn(&char) -> bool);
+    table.set_terminal_value(plus, is_plus);
+    assert!(table.terminal_value(number).is_none());
+
+    let recognizer = Recognizer::new(&cfg);
+    let input = |s: &str| s.chars().collect::<Vec<_>>();
+    assert!(recognizer.recognize_with_table(start, &input("12+345"), &table));
+    assert!(recognizer.recognize_with_table(start, &input("7"), &table));
+    assert!(!recognizer.recognize_with_table(start, &input("12+"), &table));
+    assert!(!recognizer.recognize_with_table(start, &input("1a"), &table));
+}
+
+#[test]
+fn test_explain() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, elem, sep, other) = cfg.sym();
+    cfg.rule(start).rhs([list])
+                   .rhs([other]);
+    cfg.sequence(list).separator(Proper(sep)).rhs(elem, 1..);
+    cfg.rewrite_sequences();
+
+    let recognizer = Recognizer::new(&cfg);
+    // start ::= list; list ::= elem; list ::= list sep elem
+    assert_eq!(recognizer.explain(start, &[elem, sep, elem]), Some(vec![0, 2, 3]));
+    assert_eq!(recognizer.explain(start, &[elem]), Some(vec![0, 2]));
+    assert_eq!(recognizer.explain(start, &[elem, sep, elem, sep, elem]), Some(vec![0, 2, 3, 3]));
+    assert_eq!(recognizer.explain(start, &[elem, sep]), None);
+}
+
+#[test]
+fn test_cyk_recognize() {
+    // Balanced parentheses in CNF.
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (lp, rp, pairs, closed, l, r) = cfg.sym();
+    cfg.rule(start).rhs([]).rhs([l, r]).rhs([l, closed]).rhs([pairs, pairs])
+       .rule(pairs).rhs([l, r]).rhs([l, closed]).rhs([pairs, pairs])
+       .rule(closed).rhs([pairs, r])
+       .rule(l).rhs([lp])
+       .rule(r).rhs([rp]);
+
+    let recognizer = Recognizer::new(&cfg);
+    let inputs: &[&[_]] = &[&[], &[lp, rp], &[lp, lp, rp, rp], &[lp, rp, lp, rp],
+                            &[lp, lp, rp, lp, rp, rp], &[lp], &[rp, lp], &[lp, rp, rp],
+                            &[lp, lp, rp]];
+    for input in inputs {
+        assert_eq!(cfg.cyk_recognizes(start, input), recognizer.recognize(start, input));
+    }
+    assert!(cfg.cyk_recognizes(start, &[lp, lp, rp, lp, rp, rp]));
+    assert!(!cfg.cyk_recognizes(start, &[lp, rp, rp]));
+}
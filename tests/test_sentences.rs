@@ -0,0 +1,30 @@
+extern crate cfg;
+
+mod support;
+
+use cfg::*;
+use cfg::symbol::ConsecutiveSymbols;
+
+#[test]
+fn test_from_sentences() {
+    let mut sym_source = ConsecutiveSymbols::new();
+    let (a, b, c) = sym_source.sym();
+    let sentences = vec![vec![a, b, c], vec![a, b], vec![a, c], vec![b], vec![], vec![b]];
+
+    let cfg: Cfg = Cfg::from_sentences(sym_source, &sentences);
+
+    let mut equivalent: Cfg = Cfg::new();
+    let start = equivalent.start_sym();
+    let (a, b, c, g0, g1) = equivalent.sym();
+
+    // Order is significant.
+    equivalent.rule(start).rhs([]).rhs([a, g0]).rhs([b])
+              .rule(g0).rhs([b, g1]).rhs([c])
+              .rule(g1).rhs([]).rhs([c]);
+
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+
+    let language = support::language(cfg.rules(), start, 4);
+    assert_eq!(language, sentences.into_iter().collect());
+    assert!(!language.contains(&vec![a, c, c]));
+}
@@ -2,8 +2,22 @@ extern crate cfg;
 
 mod support;
 
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::u32;
+
 use cfg::*;
+use cfg::cycles::Cycles;
+use cfg::history::{Action, Generated, NullHistory, RewriteSequence, SequenceOrigin};
+use cfg::recognizer::Recognizer;
+use cfg::sequence::{sequences_equivalent, LeftRecGroup, Recursion, Sequence};
+use cfg::sequence::Separator;
 use cfg::sequence::Separator::*;
+use cfg::rule_container::{RuleContainer, RuleVec};
+use cfg::sequence_builder::SequenceRuleBuilder;
+use cfg::sequence_destination::{ExpansionStrategy, PartialSequence, ReduceStats};
+use cfg::sequence_destination::{FnDestination, SequencesToProductions};
+use cfg::sequence_destination::{NullableSeparator, SequenceError, SequenceErrorKind};
+use cfg::symbol::{ConsecutiveSymbols, GrammarSymbol, NamedSymbols, NumericSymbol, SymbolKind};
 use cfg::usefulness::Usefulness;
 
 #[test]
@@ -57,3 +71,1699 @@ fn test_sequence_combinations() {
         assert!(!Usefulness::new(&mut cfg).has_useless_rules());
     }
 }
+
+#[test]
+fn test_proper_separator_placement() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (elem, sep) = cfg.sym();
+
+    cfg.sequence(start).separator(Proper(sep)).inclusive(3, Some(5))
+                       .rhs_with_history(elem, NullHistory);
+    cfg.rewrite_sequences();
+
+    let expected = (3..6).map(|n| {
+        let mut sentence = vec![elem];
+        for _ in 1..n {
+            sentence.push(sep);
+            sentence.push(elem);
+        }
+        sentence
+    }).collect();
+    assert_eq!(support::language(cfg.rules(), start, 12), expected);
+    assert!(!Usefulness::new(&mut cfg).has_useless_rules());
+}
+
+#[test]
+fn test_sequence_bounds() {
+    for start in 0..6 {
+        for end in start..8 {
+            let mut cfg: Cfg = Cfg::new();
+            let top = cfg.start_sym();
+            let (elem, sep) = cfg.sym();
+            let (unseparated, separated) = cfg.sym();
+
+            cfg.rule(top).rhs([unseparated]).rhs([separated]);
+            cfg.sequence(unseparated).inclusive(start, Some(end))
+                                     .rhs_with_history(elem, NullHistory);
+            cfg.sequence(separated).separator(Proper(sep)).inclusive(start, Some(end))
+                                   .rhs_with_history(elem, NullHistory);
+            cfg.rewrite_sequences();
+
+            let expected = (start..end + 1).map(|n| vec![elem; n as usize]).collect();
+            assert_eq!(support::language(cfg.rules(), unseparated, 16), expected);
+            let expected = (start..end + 1).map(|n| {
+                let mut sentence = vec![elem; n as usize];
+                for i in 1..n as usize {
+                    sentence.insert(2 * i - 1, sep);
+                }
+                sentence
+            }).collect();
+            assert_eq!(support::language(cfg.rules(), separated, 16), expected);
+        }
+    }
+}
+
+#[test]
+fn test_prefix_separator() {
+    let mut cfg: Cfg = Cfg::new();
+    let sep: NumericSymbol = cfg.sym();
+
+    assert_eq!(Proper(sep).prefix_separator(), Trailing(sep));
+    assert_eq!(Liberal(sep).prefix_separator(), Trailing(sep));
+    assert_eq!(Trailing(sep).prefix_separator(), Trailing(sep));
+    assert_eq!(Null::<u32>.prefix_separator(), Null);
+}
+
+#[test]
+fn test_accepts_empty() {
+    let mut cfg: Cfg = Cfg::new();
+    let (star, plus, elem) = cfg.sym();
+
+    cfg.sequence(star).inclusive(0, None).rhs_with_history(elem, NullHistory);
+    cfg.sequence(plus).inclusive(1, None).rhs_with_history(elem, NullHistory);
+    cfg.rewrite_sequences();
+
+    assert!(cfg.accepts_empty(star));
+    assert!(!cfg.accepts_empty(plus));
+    assert!(!cfg.accepts_empty(elem));
+}
+
+#[test]
+fn test_rewrite_with_map() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (elem, plus) = cfg.sym();
+
+    cfg.rule(plus).rhs([elem]).rhs([plus, elem]);
+    let num_syms = cfg.num_syms();
+
+    let mut map = HashMap::new();
+    map.insert(PartialSequence { rhs: elem, start: 1, end: None, separator: Null }, plus);
+    SequencesToProductions::with_map(&mut cfg, map).rewrite(Sequence {
+        lhs: start,
+        rhs: elem,
+        start: 0,
+        end: None,
+        separator: Null,
+        history: NullHistory,
+    });
+
+    let mut equivalent: Cfg = Cfg::new();
+    let start = equivalent.start_sym();
+    let (elem, plus) = equivalent.sym();
+
+    equivalent.rule(plus).rhs([elem]).rhs([plus, elem])
+              .rule(start).rhs([]).rhs([plus]);
+
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+    assert_eq!(cfg.num_syms(), num_syms);
+}
+
+#[test]
+fn test_generated_rules() {
+    let mut cfg: Cfg<Generated<NullHistory>> = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, elem, sep) = cfg.sym();
+
+    cfg.rule(start).rhs([list]);
+    cfg.sequence(list).separator(Liberal(sep)).rhs(elem, 2..5);
+    cfg.rewrite_sequences();
+
+    let generated = cfg.rules().map(|rule| rule.history().is_generated()).collect::<Vec<_>>();
+    assert_eq!(generated[0], false);
+    assert!(generated.len() > 2);
+    assert!(generated[1..].iter().all(|&is_generated| is_generated));
+    assert!(!NullHistory.is_generated());
+    assert!(!Generated::new(NullHistory).is_generated());
+
+    // Binarization generates the rules for the split-off parts.
+    let mut cfg: Cfg<Generated<NullHistory>> = Cfg::new();
+    let start = cfg.start_sym();
+    let (a, b, c) = cfg.sym();
+    cfg.rule(start).rhs([a, b, c]);
+    let binarized = cfg.binarize();
+    let generated = binarized.rules().filter(|rule| rule.history().is_generated()).count();
+    assert_eq!((binarized.rules().count(), generated), (2, 1));
+}
+
+#[derive(Clone, Debug, Default)]
+struct Origin(Option<(usize, u32, Option<u32>)>);
+
+impl Action for Origin {
+    fn no_op(&self) -> Self { Origin(None) }
+
+    fn sequence_part<S>(&self, origin: &SequenceOrigin<S>) -> Self where S: GrammarSymbol {
+        Origin(Some((origin.lhs.usize(), origin.start, origin.end)))
+    }
+}
+
+impl RewriteSequence for Origin {
+    type Rewritten = Origin;
+
+    fn sequence<H, S>(&self, top: &Sequence<H, S>) -> Self where S: GrammarSymbol {
+        Origin(Some((top.lhs.usize(), top.start, top.end)))
+    }
+}
+
+#[test]
+fn test_sequence_origin() {
+    let mut cfg: Cfg<Origin> = Cfg::new();
+    let (list, elem) = cfg.sym::<(_, _)>();
+
+    cfg.sequence(list).rhs(elem, 3..4);
+    cfg.rewrite_sequences();
+
+    let mut origins = cfg.rules().map(|rule| rule.history().0.unwrap()).collect::<Vec<_>>();
+    origins.sort();
+    let list = list.usize();
+    assert_eq!(origins, vec![(list, 1, Some(1)), (list, 2, Some(2)), (list, 3, Some(3))]);
+}
+
+#[test]
+fn test_helper_names() {
+    let mut cfg: Cfg<NullHistory, NullHistory, NamedSymbols> = Cfg::with_sym_source(
+        NamedSymbols::new()
+    );
+    let list = cfg.sym_named("list");
+    let item = cfg.sym_named("item");
+    let comma = cfg.sym_named("comma");
+
+    cfg.sequence(list).intersperse(comma).rhs(item, 0..);
+    cfg.rewrite_sequences();
+
+    let helper = cfg.rules().find(|rule| rule.lhs() == list && rule.rhs().len() == 1).unwrap();
+    let helper = helper.rhs()[0];
+    assert_eq!(cfg.name_of(list), Some("list"));
+    assert_eq!(cfg.name_of(helper), Some("item{1,} % comma"));
+    assert_eq!(cfg.sym_named("item{1,} % comma"), helper);
+}
+
+#[test]
+fn test_symbol_kinds() {
+    let mut cfg: Cfg<Generated<NullHistory>> = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, elem) = cfg.sym();
+
+    cfg.rule(start).rhs([list]);
+    cfg.sequence(list).rhs(elem, 2..4);
+    cfg.rewrite_sequences();
+
+    let kinds = cfg.symbols().collect::<Vec<_>>();
+    assert!(kinds.len() > 3);
+    assert_eq!(kinds[0], (start, SymbolKind::Nonterminal));
+    assert_eq!(kinds[1], (list, SymbolKind::Nonterminal));
+    assert_eq!(kinds[2], (elem, SymbolKind::Terminal));
+    assert!(kinds[3..].iter().all(|&(_, kind)| kind == SymbolKind::Generated));
+}
+
+#[test]
+fn test_finite_symbols() {
+    let mut cfg: Cfg = Cfg::new();
+    let (bounded, star, elem) = cfg.sym();
+
+    cfg.sequence(bounded).rhs(elem, 2..6);
+    cfg.sequence(star).rhs(elem, 0..);
+    cfg.rewrite_sequences();
+
+    let finite = cfg.finite_symbols();
+    assert!(finite.contains(bounded));
+    assert!(!finite.contains(star));
+    assert!(!finite.contains(elem));
+    let infinite = cfg.rules().map(|rule| rule.lhs())
+                              .filter(|&lhs| !finite.contains(lhs))
+                              .collect::<BTreeSet<_>>();
+    // The star and its nonempty part.
+    assert_eq!(infinite.len(), 2);
+    assert!(infinite.contains(&star));
+}
+
+#[test]
+fn test_mandatory_terminals() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, star, record, item, sep, key, value) = cfg.sym();
+
+    cfg.sequence(list).intersperse(sep).rhs(item, 2..);
+    cfg.sequence(star).rhs(item, 0..);
+    // record ::= key list | key value star
+    cfg.rule(record).rhs([key, list]).rhs([key, value, star]);
+    cfg.rule(start).rhs([record]);
+    cfg.rewrite_sequences();
+
+    assert_eq!(cfg.mandatory_terminals(list).iter().collect::<Vec<_>>(), vec![item, sep]);
+    assert!(cfg.mandatory_terminals(star).is_empty());
+    assert_eq!(cfg.mandatory_terminals(start).iter().collect::<Vec<_>>(), vec![key]);
+}
+
+#[test]
+fn test_add_sequence_rule() {
+    let mut cfg: Cfg = Cfg::new();
+    let (start, list, elem, sep) = cfg.sym();
+
+    cfg.rule(start).rhs([list, elem]);
+    cfg.add_sequence_rule(list, elem, 1.., Proper(sep), NullHistory);
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (start, list, elem, sep) = equivalent.sym();
+    equivalent.rule(start).rhs([list, elem]);
+    equivalent.sequence(list).intersperse(sep).rhs(elem, 1..);
+    equivalent.rewrite_sequences();
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_treat_max_as_unbounded() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem, sep) = cfg.sym();
+    SequencesToProductions::new(&mut cfg).treat_max_as_unbounded(true).rewrite(Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 1,
+        end: Some(u32::MAX),
+        separator: Proper(sep),
+        history: NullHistory,
+    });
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (list, elem, sep) = equivalent.sym();
+    equivalent.sequence(list).intersperse(sep).rhs(elem, 1..);
+    equivalent.rewrite_sequences();
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_item_alt() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (x, y) = cfg.sym();
+    cfg.sequence(start).at_least(1).item_alt(&[&[x], &[y]]);
+    cfg.rewrite_sequences();
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(start, &[x, y, x]));
+    assert!(recognizer.recognize(start, &[y]));
+    assert!(!recognizer.recognize(start, &[]));
+
+    let mut equivalent: Cfg = Cfg::new();
+    let start = equivalent.start_sym();
+    let (x, y, alt) = equivalent.sym();
+    equivalent.rule(alt).rhs([x])
+                        .rhs([y])
+              .rule(start).rhs([alt])
+                          .rhs([start, alt]);
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_item_alt_name() {
+    let mut cfg: Cfg<NullHistory, NullHistory, NamedSymbols> = Cfg::with_sym_source(
+        NamedSymbols::new()
+    );
+    let list = cfg.sym_named("list");
+    let x = cfg.sym_named("x");
+    let y = cfg.sym_named("y");
+    let z = cfg.sym_named("z");
+
+    cfg.sequence(list).at_least(1).item_alt(&[&[x], &[y, z]]);
+    let alt = cfg.sym_named("(x | y z)");
+    cfg.rewrite_sequences();
+    assert!(cfg.rules().any(|rule| rule.lhs() == list && rule.rhs() == &[alt][..]));
+}
+
+#[test]
+fn test_separated_list() {
+    let mut cfg: Cfg = Cfg::new();
+    let (item, sep) = cfg.sym();
+    let list = cfg.separated_list(item, sep, true);
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(!recognizer.recognize(list, &[]));
+    assert!(recognizer.recognize(list, &[item]));
+    assert!(recognizer.recognize(list, &[item, sep, item, sep, item]));
+    assert!(!recognizer.recognize(list, &[item, sep]));
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (item, sep, list) = equivalent.sym();
+    equivalent.rule(list).rhs([item])
+                         .rhs([list, sep, item]);
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_separated_list_empty() {
+    let mut cfg: Cfg = Cfg::new();
+    let (item, sep) = cfg.sym();
+    let list = cfg.separated_list(item, sep, false);
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(list, &[]));
+    assert!(recognizer.recognize(list, &[item]));
+    assert!(recognizer.recognize(list, &[item, sep, item, sep, item]));
+    assert!(!recognizer.recognize(list, &[sep]));
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (item, sep, list, nonempty) = equivalent.sym();
+    equivalent.rule(list).rhs([])
+                         .rhs([nonempty]);
+    equivalent.rule(nonempty).rhs([item])
+                             .rhs([nonempty, sep, item]);
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_emit_grouped() {
+    let rules = |emit_grouped| {
+        let mut cfg: Cfg = Cfg::new();
+        let (top, elem) = cfg.sym();
+        {
+            let mut rewrite = SequencesToProductions::new(&mut cfg);
+            rewrite.set_emit_grouped(emit_grouped);
+            rewrite.rewrite(Sequence {
+                lhs: top,
+                rhs: elem,
+                start: 1,
+                end: Some(3),
+                separator: Null,
+                history: NullHistory,
+            });
+        }
+        cfg.rules().map(|rule| (rule.lhs(), rule.rhs().to_vec())).collect::<Vec<_>>()
+    };
+    let interleaved = rules(false);
+    let grouped = rules(true);
+
+    let mut sorted = interleaved.clone();
+    sorted.sort_by_key(|&(lhs, _)| lhs);
+    assert!(interleaved != sorted);
+    assert_eq!(grouped, sorted);
+}
+
+#[test]
+fn test_at_most_at_least() {
+    let seq = Sequence {
+        lhs: 1,
+        rhs: 2,
+        start: 1,
+        end: None,
+        separator: Null,
+        history: (),
+    };
+    let at_most = seq.clone().at_most(2);
+    assert_eq!((at_most.start, at_most.end), (0, Some(2)));
+    let at_least = seq.at_least(3);
+    assert_eq!((at_least.start, at_least.end), (3, None));
+
+    let mut cfg: Cfg = Cfg::new();
+    let (empty, at_most, at_least, elem) = cfg.sym();
+    cfg.sequence(empty).at_most(0).rhs_with_history(elem, NullHistory);
+    cfg.sequence(at_most).at_most(2).rhs_with_history(elem, NullHistory);
+    cfg.sequence(at_least).at_least(3).rhs_with_history(elem, NullHistory);
+    cfg.rewrite_sequences();
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (empty, at_most, at_least, elem) = equivalent.sym();
+    equivalent.sequence(empty).inclusive(0, Some(0)).rhs_with_history(elem, NullHistory);
+    equivalent.sequence(at_most).inclusive(0, Some(2)).rhs_with_history(elem, NullHistory);
+    equivalent.sequence(at_least).inclusive(3, None).rhs_with_history(elem, NullHistory);
+    equivalent.rewrite_sequences();
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+
+    let empty_rules = cfg.rules().filter(|rule| rule.lhs() == empty)
+                                 .map(|rule| rule.rhs().to_vec())
+                                 .collect::<Vec<_>>();
+    assert_eq!(empty_rules, vec![vec![]]);
+}
+
+#[test]
+fn test_on_new_symbol() {
+    let mut cfg: Cfg = Cfg::new();
+    let (top, elem, sep) = cfg.sym();
+    let mut allocated = vec![];
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        // The callback borrows a local vector.
+        rewrite.on_new_symbol(|sym, partial: &PartialSequence<_>| {
+            allocated.push((sym, partial.clone()));
+        });
+        rewrite.rewrite(Sequence {
+            lhs: top,
+            rhs: elem,
+            start: 2,
+            end: Some(7),
+            separator: Proper(sep),
+            history: NullHistory,
+        });
+    }
+
+    let distinct = allocated.iter().map(|&(_, ref partial)| partial).collect::<HashSet<_>>();
+    assert_eq!(distinct.len(), allocated.len());
+
+    // Every helper symbol has rules.
+    for &(sym, _) in allocated.iter() {
+        assert!(cfg.rules().any(|rule| rule.lhs() == sym));
+    }
+    let helpers = cfg.rules().map(|rule| rule.lhs())
+                             .filter(|&lhs| lhs != top)
+                             .collect::<BTreeSet<_>>();
+    assert_eq!(helpers.len(), allocated.len());
+}
+
+#[test]
+fn test_mark_optionals() {
+    let mut cfg: Cfg = Cfg::new();
+    let (optional, list, elem) = cfg.sym();
+    cfg.sequence(optional).inclusive(0, Some(1)).rhs_with_history(elem, NullHistory);
+    cfg.sequence(list).rhs(elem, 0..);
+    cfg.rewrite_sequences();
+
+    // A star is an optional plus.
+    let optionals = cfg.mark_optionals();
+    assert_eq!(optionals.iter().map(|&(lhs, _)| lhs).collect::<Vec<_>>(), vec![optional, list]);
+    let (_, sym) = optionals[0];
+    let sym_rules = cfg.rules().filter(|rule| rule.lhs() == sym)
+                               .map(|rule| rule.rhs().to_vec())
+                               .collect::<Vec<_>>();
+    assert_eq!(sym_rules, vec![vec![elem]]);
+}
+
+#[test]
+fn test_recursion_per_sequence() {
+    let mut cfg: Cfg = Cfg::new();
+    let (left, right, elem, sep) = cfg.sym();
+    cfg.sequence(left).intersperse(sep).rhs(elem, 1..);
+    cfg.sequence(right).intersperse(sep).recursion(Recursion::Right).rhs(elem, 1..);
+    cfg.rewrite_sequences();
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (left, right, elem, sep) = equivalent.sym();
+    equivalent.rule(left).rhs([elem])
+                         .rhs([left, sep, elem]);
+    equivalent.rule(right).rhs([elem])
+                          .rhs([elem, sep, right]);
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_recursion_shared_helpers() {
+    // Returns the number of rules and whether they include right recursion.
+    let rewrite = |recursion, end| {
+        let mut cfg: Cfg = Cfg::new();
+        let (left, right, elem) = cfg.sym();
+        cfg.sequence(left).inclusive(3, end).rhs_with_history(elem, NullHistory);
+        cfg.sequence(right).recursion(recursion)
+                           .inclusive(3, end)
+                           .rhs_with_history(elem, NullHistory);
+        cfg.rewrite_sequences_shared();
+        let right_recursive = cfg.rules().any(|rule| rule.rhs() == &[elem, rule.lhs()][..]);
+        (cfg.rules().count(), right_recursive)
+    };
+    // Bounded helpers don't depend on the direction of recursion.
+    assert_eq!(rewrite(Recursion::Right, Some(9)), rewrite(Recursion::Left, Some(9)));
+    // Helpers for unlimited repetitions do.
+    assert!(!rewrite(Recursion::Left, None).1);
+    assert!(rewrite(Recursion::Right, None).1);
+}
+
+#[test]
+fn test_star_plus() {
+    let mut cfg: Cfg = Cfg::new();
+    let (a, b) = cfg.sym();
+    let a_star = cfg.star(a);
+    let b_plus = cfg.plus(b);
+
+    assert!(cfg.accepts_empty(a_star));
+    assert!(!cfg.accepts_empty(b_plus));
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(a_star, &[a, a, a]));
+    assert!(recognizer.recognize(b_plus, &[b]));
+    assert!(recognizer.recognize(b_plus, &[b, b]));
+    assert!(!recognizer.recognize(b_plus, &[]));
+    assert!(!recognizer.recognize(a_star, &[b]));
+}
+
+/// Returns the rules of a single rewrite step, the symbols of the sequence, and the next two
+/// symbols, which are allocated for helpers.
+fn reduce_once<F>(start: u32, end: Option<u32>, separator: F)
+        -> (Vec<(NumericSymbol, Vec<NumericSymbol>)>, (NumericSymbol, NumericSymbol, NumericSymbol),
+            (NumericSymbol, NumericSymbol)) where
+            F: Fn(NumericSymbol) -> Separator<NumericSymbol> {
+    let mut cfg: Cfg = Cfg::new();
+    let (lhs, elem, sep) = cfg.sym();
+    let helpers = cfg.clone().sym();
+    let mut rewrite: SequencesToProductions<NullHistory, _> = SequencesToProductions::new(&mut cfg);
+    let rules = rewrite.reduce_once(Sequence {
+        lhs: lhs,
+        rhs: elem,
+        start: start,
+        end: end,
+        separator: separator(sep),
+        history: NullHistory,
+    });
+    (rules, (lhs, elem, sep), helpers)
+}
+
+#[test]
+fn test_reduce_once() {
+    let (rules, (lhs, _, _), (proper, trailing)) = reduce_once(1, None, Liberal);
+    assert_eq!(rules, vec![(lhs, vec![proper]), (lhs, vec![trailing])]);
+
+    let (rules, (lhs, _, sep), (proper, _)) = reduce_once(1, None, Trailing);
+    assert_eq!(rules, vec![(lhs, vec![proper, sep])]);
+
+    let (rules, (lhs, _, _), (nonempty, _)) = reduce_once(0, Some(3), |_| Null);
+    assert_eq!(rules, vec![(lhs, vec![]), (lhs, vec![nonempty])]);
+
+    let (rules, (lhs, _, _), _) = reduce_once(0, Some(0), |_| Null);
+    assert_eq!(rules, vec![(lhs, vec![])]);
+
+    let (rules, (lhs, elem, sep), _) = reduce_once(1, None, Proper);
+    assert_eq!(rules, vec![(lhs, vec![elem]), (lhs, vec![lhs, sep, elem])]);
+
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem) = cfg.sym();
+    let sequence = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 0,
+        end: Some(3),
+        separator: Null,
+        history: NullHistory,
+    };
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg).share_helpers(true);
+        assert_eq!(rewrite.reduce_once(sequence.clone()).len(), 2);
+        rewrite.rewrite(sequence);
+    }
+    let recognizer = Recognizer::new(&cfg);
+    for n in 0..4 {
+        assert!(recognizer.recognize(list, &vec![elem; n]));
+    }
+    assert!(!recognizer.recognize(list, &[elem; 4]));
+}
+
+#[test]
+fn test_reduce_stats() {
+    let mut cfg: Cfg = Cfg::new();
+    let (pair, list, elem, sep) = cfg.sym();
+    let mut rewrite = SequencesToProductions::new(&mut cfg);
+    let sequence = Sequence {
+        lhs: pair,
+        rhs: elem,
+        start: 2,
+        end: Some(2),
+        separator: Liberal(sep),
+        history: NullHistory,
+    };
+    rewrite.rewrite(sequence.clone());
+    // The trailing sequence shares its properly separated pair with the proper sequence.
+    assert_eq!(rewrite.reduce_stats(), ReduceStats {
+        liberal: 1,
+        trailing: 1,
+        small_block: 1,
+        ..ReduceStats::default()
+    });
+
+    rewrite.rewrite(Sequence { lhs: list, ..sequence }.inclusive(0, Some(3)));
+    assert_eq!(rewrite.reduce_stats(), ReduceStats {
+        liberal: 2,
+        trailing: 3,
+        epsilon: 1,
+        small_block: 3,
+        block: 1,
+        span: 2,
+        ..ReduceStats::default()
+    });
+}
+
+#[test]
+fn test_rewrite_stats() {
+    let stats = |end| {
+        let mut cfg: Cfg = Cfg::new();
+        let (list, elem) = cfg.sym();
+        let num_syms = cfg.num_syms();
+        let stats = {
+            let mut rewrite = SequencesToProductions::new(&mut cfg);
+            rewrite.rewrite(Sequence {
+                lhs: list,
+                rhs: elem,
+                start: 2,
+                end: Some(end),
+                separator: Null,
+                history: NullHistory,
+            });
+            rewrite.rewrite_stats()
+        };
+        assert_eq!(stats.aux_symbols, cfg.num_syms() - num_syms);
+        assert_eq!(stats.rules_emitted, cfg.rules().count());
+        stats
+    };
+    let (short, long) = (stats(5), stats(8));
+    assert!(short.aux_symbols < long.aux_symbols);
+    assert!(short.rules_emitted < long.rules_emitted);
+}
+
+#[test]
+fn test_separator_string() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, trailing, elem, comma, space) = cfg.sym();
+    let mut equivalent = cfg.clone();
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        let sep = rewrite.separator_string(&[comma, space]);
+        assert_eq!(rewrite.separator_string(&[comma, space]), sep);
+        let sequence = Sequence {
+            lhs: list,
+            rhs: elem,
+            start: 1,
+            end: None,
+            separator: Proper(sep),
+            history: NullHistory,
+        };
+        rewrite.rewrite(sequence.clone());
+        rewrite.rewrite(Sequence { lhs: trailing, separator: Trailing(sep), ..sequence });
+    }
+    let (sep, inner) = equivalent.sym::<(NumericSymbol, _)>();
+    // Order is significant.
+    equivalent.rule(sep).rhs([comma, space])
+              .rule(list).rhs([elem])
+                         .rhs([list, sep, elem])
+              .rule(trailing).rhs([inner, sep])
+              .rule(inner).rhs([elem])
+                          .rhs([inner, sep, elem]);
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+
+    // The rule of the separator is generated again after a failed rewrite.
+    let mut cfg: Cfg<Generated<NullHistory>> = Cfg::new();
+    let (list, elem, comma, space) = cfg.sym();
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        let sep = rewrite.separator_string(&[comma, space]);
+        let sequence = Sequence {
+            lhs: list,
+            rhs: elem,
+            start: 1,
+            end: Some(20),
+            separator: Proper(sep),
+            history: Generated::new(NullHistory),
+        };
+        rewrite.set_max_rules(Some(3));
+        assert!(rewrite.try_rewrite(sequence.clone()).is_err());
+        rewrite.set_max_rules(None);
+        assert_eq!(rewrite.try_rewrite(sequence), Ok(()));
+    }
+    let separators = cfg.rules().filter(|rule| rule.rhs() == &[comma, space][..])
+                                .map(|rule| rule.history().is_generated())
+                                .collect::<Vec<_>>();
+    assert_eq!(separators, vec![true]);
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(list, &[elem, comma, space, elem]));
+    assert!(!recognizer.recognize(list, &[elem, comma, elem]));
+}
+
+#[test]
+fn test_exclusive() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, plus, elem, sep) = cfg.sym();
+    let (mut sequences, mut inclusive) = (vec![], vec![]);
+    SequenceRuleBuilder::new(&mut sequences)
+        .sequence(list).intersperse(sep).exclusive(2, 5).rhs_with_history(elem, NullHistory)
+        .sequence(plus).at_least(1).rhs_with_history(elem, NullHistory);
+    SequenceRuleBuilder::new(&mut inclusive)
+        .sequence(list).intersperse(sep).inclusive(2, Some(4)).rhs_with_history(elem, NullHistory)
+        .sequence(plus).inclusive(1, None).rhs_with_history(elem, NullHistory);
+    let bounds = |sequences: &[Sequence<NullHistory, NumericSymbol>]| {
+        sequences.iter().map(|seq| (seq.lhs, seq.start, seq.end)).collect::<Vec<_>>()
+    };
+    assert_eq!(bounds(&sequences), bounds(&inclusive));
+
+    let mut equivalent = cfg.clone();
+    SequencesToProductions::rewrite_sequences(&sequences[..], &mut cfg);
+    SequencesToProductions::rewrite_sequences(&inclusive[..], &mut equivalent);
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_range() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem) = cfg.sym();
+    let mut sequences = vec![];
+    SequenceRuleBuilder::new(&mut sequences)
+        .sequence(list).range(..3).rhs_with_history(elem, NullHistory)
+        .sequence(list).range(2..).rhs_with_history(elem, NullHistory)
+        .sequence(list).range(2..5).rhs_with_history(elem, NullHistory)
+        .sequence(list).range(..).rhs_with_history(elem, NullHistory);
+    let bounds = sequences.iter().map(|seq| (seq.start, seq.end)).collect::<Vec<_>>();
+    assert_eq!(bounds, vec![(0, Some(2)), (2, None), (2, Some(4)), (0, None)]);
+}
+
+#[test]
+#[should_panic]
+fn test_exclusive_empty() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem) = cfg.sym();
+    cfg.sequence(list).exclusive(0, 0).rhs_with_history(elem, NullHistory);
+}
+
+#[test]
+fn test_left_recursion_groups() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, star, elem, sep, other) = cfg.sym();
+    cfg.sequence(list).intersperse(sep).rhs(elem, 1..);
+    cfg.sequence(star).rhs(elem, 0..);
+    cfg.rewrite_sequences();
+    // Unrelated rule groups.
+    cfg.rule(start).rhs([list]).rhs([start, list]).rhs([star])
+       .rule(other).rhs([elem]).rhs([other, other, elem]);
+
+    let plus = cfg.rules().find(|rule| rule.lhs() == star && rule.rhs().len() == 1
+                                       && rule.rhs()[0] != elem).unwrap().rhs()[0];
+    assert_eq!(cfg.left_recursion_groups(), vec![
+        LeftRecGroup { lhs: list, item: elem, separator: Some(sep) },
+        LeftRecGroup { lhs: plus, item: elem, separator: None },
+    ]);
+}
+
+#[test]
+fn test_linear_expansion() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, star, elem, sep) = cfg.sym();
+    let mut equivalent = cfg.clone();
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        rewrite.set_expansion_strategy(ExpansionStrategy::Linear);
+        SequenceRuleBuilder::new(rewrite)
+            .sequence(list).intersperse(sep).inclusive(2, Some(4))
+                           .rhs_with_history(elem, NullHistory)
+            .sequence(star).separator(Null).at_least(3).rhs_with_history(elem, NullHistory);
+    }
+    let plus = equivalent.sym();
+    // Order is significant.
+    equivalent.rule(list).rhs([elem, sep, elem])
+                         .rhs([elem, sep, elem, sep, elem])
+                         .rhs([elem, sep, elem, sep, elem, sep, elem])
+              .rule(star).rhs([elem, elem, plus])
+              .rule(plus).rhs([elem])
+                         .rhs([plus, elem]);
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_right_linear() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, star, elem) = cfg.sym();
+    let mut equivalent = cfg.clone();
+    {
+        let rewrite = SequencesToProductions::new(&mut cfg).right_linear(true);
+        SequenceRuleBuilder::new(rewrite)
+            .sequence(list).inclusive(2, Some(3)).rhs_with_history(elem, NullHistory)
+            .sequence(star).at_least(0).rhs_with_history(elem, NullHistory);
+    }
+    let (one_or_two, zero_or_one, zero) = equivalent.sym();
+    // Order is significant.
+    equivalent.rule(list).rhs([elem, one_or_two])
+              .rule(one_or_two).rhs([elem, zero_or_one])
+              .rule(zero_or_one).rhs([]).rhs([elem, zero])
+              .rule(zero).rhs([])
+              .rule(star).rhs([]).rhs([elem, star]);
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+
+    let dfa = cfg.to_dfa(list).unwrap();
+    assert!(dfa.accepts(&[elem, elem]) && dfa.accepts(&[elem, elem, elem]));
+    assert!(!dfa.accepts(&[elem]) && !dfa.accepts(&[elem, elem, elem, elem]));
+}
+
+#[test]
+fn test_rule_predicates() {
+    let mut cfg: Cfg = Cfg::new();
+    let (star, elem) = cfg.sym();
+    cfg.sequence(star).rhs(elem, 0..);
+    cfg.rewrite_sequences();
+
+    // star ::= epsilon | plus
+    // plus ::= elem | plus elem
+    let rules = cfg.rules().collect::<Vec<_>>();
+    let lengths = rules.iter().map(|rule| rule.rhs_len()).collect::<Vec<_>>();
+    assert_eq!(lengths, vec![0, 1, 1, 2]);
+    assert!(rules[0].is_nullable_rule() && !rules[0].is_unary());
+    assert!(rules[1].is_unary() && !rules[1].is_nullable_rule());
+    assert!(!rules[3].is_unary() && !rules[3].is_nullable_rule());
+}
+
+#[test]
+fn test_rules_with_repeats() {
+    let mut cfg: Cfg = Cfg::new();
+    let (pair, elem, sep) = cfg.sym();
+    cfg.rule(pair).rhs([elem, sep]);
+    cfg.sequence(pair).intersperse(sep).inclusive(2, Some(2)).rhs_with_history(elem, NullHistory);
+    cfg.rewrite_sequences();
+
+    // pair ::= elem sep elem
+    assert_eq!(cfg.rules().nth(1).unwrap().rhs(), &[elem, sep, elem]);
+    assert_eq!(cfg.rules_with_repeats(), vec![1]);
+}
+
+#[test]
+fn test_sequence_of_nonterminal() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, item, a, b, c, sep) = cfg.sym();
+    cfg.rule(item).rhs([a, b])
+                  .rhs([c])
+                  .rhs([item, c]);
+    cfg.sequence(list).intersperse(sep).rhs(item, 1..);
+    cfg.rewrite_sequences();
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (list, item, a, b, c, sep) = equivalent.sym();
+    equivalent.rule(item).rhs([a, b])
+                         .rhs([c])
+                         .rhs([item, c]);
+    equivalent.rule(list).rhs([item])
+                         .rhs([list, sep, item]);
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(list, &[a, b]));
+    assert!(recognizer.recognize(list, &[a, b, c, sep, c, sep, a, b]));
+    assert!(!recognizer.recognize(list, &[a, b, sep]));
+    assert!(!recognizer.recognize(list, &[a, sep, b]));
+}
+
+#[test]
+fn test_liberal_parts() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem, sep) = cfg.sym();
+    let parts = {
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        rewrite.rewrite(Sequence {
+            lhs: list,
+            rhs: elem,
+            start: 0,
+            end: None,
+            separator: Liberal(sep),
+            history: NullHistory,
+        });
+        assert_eq!(rewrite.liberal_parts(elem), None);
+        rewrite.liberal_parts(list).unwrap()
+    };
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(parts.proper, &[elem, sep, elem]));
+    assert!(!recognizer.recognize(parts.proper, &[elem, sep]));
+    assert!(recognizer.recognize(parts.trailing, &[elem, sep, elem, sep]));
+    assert!(!recognizer.recognize(parts.trailing, &[elem, sep, elem]));
+    assert!(recognizer.recognize(list, &[elem, sep, elem]));
+    assert!(recognizer.recognize(list, &[elem, sep, elem, sep]));
+}
+
+#[test]
+fn test_right_linear_symbols() {
+    let mut cfg: Cfg = Cfg::new();
+    let (left, right, elem) = cfg.sym();
+    cfg.sequence(left).rhs(elem, 0..);
+    cfg.sequence(right).recursion(Recursion::Right).rhs(elem, 0..);
+    cfg.rewrite_sequences();
+
+    let right_linear = cfg.right_linear_symbols();
+    assert!(right_linear.contains(right));
+    assert!(!right_linear.contains(left));
+    assert!(!right_linear.contains(elem));
+}
+
+#[test]
+fn test_bounded_repeat() {
+    for &separated in &[false, true] {
+        for min in 0 .. 5 {
+            for max in min .. 9 {
+                let mut cfg: Cfg = Cfg::new();
+                let (item, a, b, c, sep) = cfg.sym();
+                cfg.rule(item).rhs([a])
+                              .rhs([b, c]);
+                let separator = if separated { Proper(sep) } else { Null };
+                let top = cfg.bounded_repeat(item, min, max, separator);
+
+                let recognizer = Recognizer::new(&cfg);
+                for len in 0 .. max + 2 {
+                    let mut input = vec![];
+                    for i in 0 .. len {
+                        if separated && i != 0 {
+                            input.push(sep);
+                        }
+                        if i % 2 == 0 {
+                            input.push(a);
+                        } else {
+                            input.extend(&[b, c]);
+                        }
+                    }
+                    assert_eq!(recognizer.recognize(top, &input), min <= len && len <= max,
+                               "{} repetitions of {} ..= {}, separated: {}",
+                               len, min, max, separated);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_bounded_repeat_max() {
+    let mut cfg: Cfg = Cfg::new();
+    let item = cfg.sym();
+    let top = cfg.bounded_repeat(item, 2, u32::MAX, Null);
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(!recognizer.recognize(top, &[item]));
+    assert!(recognizer.recognize(top, &[item; 2]));
+    assert!(recognizer.recognize(top, &[item; 30]));
+}
+
+#[test]
+#[should_panic]
+fn test_bounded_repeat_invalid_bounds() {
+    let mut cfg: Cfg = Cfg::new();
+    let item = cfg.sym();
+    cfg.bounded_repeat(item, 3, 2, Null);
+}
+
+#[test]
+fn test_liberal_with_zero_lower_bound() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, trailing, elem, sep) = cfg.sym();
+    cfg.sequence(list).separator(Liberal(sep)).rhs(elem, 0..4);
+    cfg.sequence(trailing).separator(Trailing(sep)).rhs(elem, 0..);
+    cfg.rewrite_sequences();
+
+    {
+        let recognizer = Recognizer::new(&cfg);
+        let accepted: &[&[NumericSymbol]] = &[
+            &[],
+            &[elem],
+            &[elem, sep],
+            &[elem, sep, elem],
+            &[elem, sep, elem, sep],
+            &[elem, sep, elem, sep, elem],
+            &[elem, sep, elem, sep, elem, sep],
+        ];
+        let rejected: &[&[NumericSymbol]] = &[
+            &[sep],
+            &[sep, elem],
+            &[elem, elem],
+            &[elem, sep, sep],
+            &[elem, sep, elem, sep, elem, sep, elem],
+        ];
+        for input in accepted {
+            assert!(recognizer.recognize(list, input));
+        }
+        for input in rejected {
+            assert!(!recognizer.recognize(list, input));
+        }
+        assert!(recognizer.recognize(trailing, &[]));
+        assert!(recognizer.recognize(trailing, &[elem, sep]));
+        assert!(!recognizer.recognize(trailing, &[sep]));
+    }
+
+    // Only the sequences themselves are nullable, so their parts can't form unit cycles.
+    assert!(!Cycles::new(&mut cfg).has_cycles());
+    let nullable = cfg.rules().filter(|rule| rule.rhs().is_empty()).map(|rule| rule.lhs());
+    assert_eq!(nullable.collect::<Vec<_>>(), vec![list, trailing]);
+}
+
+#[test]
+fn test_sequence_getters() {
+    let mut cfg: Cfg<u32, u32> = Cfg::new();
+    let (list, elem, sep) = cfg.sym();
+    let sequence = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 2,
+        end: Some(5),
+        separator: Proper(sep),
+        history: 7,
+    };
+    assert_eq!(sequence.lhs(), list);
+    assert_eq!(sequence.rhs(), elem);
+    assert_eq!(sequence.start(), 2);
+    assert_eq!(sequence.end(), Some(5));
+    assert_eq!(sequence.separator(), Proper(sep));
+    assert_eq!(*sequence.history(), 7);
+}
+
+#[test]
+fn test_helper_bounds() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem) = cfg.sym();
+    let (lower, upper): (NumericSymbol, NumericSymbol) = cfg.clone().sym();
+    let mut rewrite = SequencesToProductions::new(&mut cfg);
+    rewrite.rewrite(Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 1,
+        end: Some(8),
+        separator: Null,
+        history: NullHistory,
+    });
+    assert_eq!(rewrite.helper_bounds(lower), Some((1, Some(4))));
+    assert_eq!(rewrite.helper_bounds(upper), Some((5, Some(8))));
+    assert_eq!(rewrite.helper_bounds(list), None);
+    assert_eq!(rewrite.helper_bounds(elem), None);
+}
+
+#[test]
+fn test_rewriter_recursion() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem, sep) = cfg.sym();
+    let sequence = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 1,
+        end: None,
+        separator: Proper(sep),
+        history: NullHistory,
+    };
+    let mut left = cfg.clone();
+    SequencesToProductions::new(&mut left).rewrite(sequence.clone());
+    SequencesToProductions::new(&mut cfg).recursion(Recursion::Right).rewrite(sequence);
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (list, elem, sep) = equivalent.sym();
+    equivalent.rule(list).rhs([elem])
+                         .rhs([elem, sep, list]);
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (list, elem, sep) = equivalent.sym();
+    equivalent.rule(list).rhs([elem])
+                         .rhs([list, sep, elem]);
+    support::assert_eq_rules(equivalent.rules(), left.rules());
+}
+
+#[test]
+fn test_rewrite_sequences_shared() {
+    let mut cfg: Cfg = Cfg::new();
+    let (first, second, elem, sep) = cfg.sym();
+    cfg.sequence(first).intersperse(sep).rhs(elem, 2..6);
+    cfg.sequence(second).intersperse(sep).rhs(elem, 2..6);
+    let mut separate = cfg.clone();
+    separate.rewrite_sequences();
+    cfg.rewrite_sequences_shared();
+
+    let rhs_of = |cfg: &Cfg, lhs| {
+        cfg.rules().filter(|rule| rule.lhs() == lhs).map(|rule| rule.rhs().to_vec())
+                   .collect::<Vec<_>>()
+    };
+    assert_eq!(rhs_of(&cfg, first), rhs_of(&cfg, second));
+    assert!(rhs_of(&separate, first) != rhs_of(&separate, second));
+    let top_rules = rhs_of(&cfg, second).len();
+    // Only the rules for the sequences themselves aren't shared.
+    assert_eq!(cfg.rules().count(), separate.rules().count() / 2 + top_rules);
+
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(second, &[elem, sep, elem, sep, elem]));
+    assert!(!recognizer.recognize(second, &[elem]));
+}
+
+#[test]
+fn test_rewrite_iter() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem) = cfg.sym();
+    let sequence = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 1,
+        end: Some(8),
+        separator: Null,
+        history: NullHistory,
+    };
+    let mut eager = cfg.clone();
+    SequencesToProductions::new(&mut eager).rewrite(sequence.clone());
+
+    let rules = {
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        let rules = rewrite.rewrite_iter(sequence.clone()).map(|rule| (rule.lhs(), rule.rhs))
+                                                          .collect::<Vec<_>>();
+        assert_eq!(rewrite.rewrite_iter(sequence.clone()).take(2).count(), 2);
+        rules
+    };
+    assert_eq!(cfg.rules().count(), 0);
+    let eager_rules = eager.rules().map(|rule| (rule.lhs(), rule.rhs().to_vec()))
+                                   .collect::<Vec<_>>();
+    assert_eq!(rules, eager_rules);
+
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg).share_helpers(true);
+        assert_eq!(rewrite.rewrite_iter(sequence.clone()).take(2).count(), 2);
+        rewrite.rewrite(sequence);
+    }
+    let recognizer = Recognizer::new(&cfg);
+    for n in 1..9 {
+        assert!(recognizer.recognize(list, &vec![elem; n]));
+    }
+    assert!(!recognizer.recognize(list, &[elem; 9]));
+}
+
+#[test]
+fn test_try_rewrite() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem) = cfg.sym();
+    let sequence = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 1,
+        end: Some(1000),
+        separator: Null,
+        history: NullHistory,
+    };
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        assert_eq!(rewrite.try_rewrite(sequence.clone().inclusive(3, Some(2))),
+                   Err(SequenceError {
+                       lhs: list,
+                       start: 3,
+                       end: Some(2),
+                       kind: SequenceErrorKind::InvalidBounds,
+                   }));
+        rewrite.set_max_rules(Some(10));
+        assert_eq!(rewrite.try_rewrite(sequence.clone()),
+                   Err(SequenceError {
+                       lhs: list,
+                       start: 1,
+                       end: Some(1000),
+                       kind: SequenceErrorKind::TooManyRules(10),
+                   }));
+    }
+    assert_eq!(cfg.rules().count(), 0);
+
+    let mut eager = cfg.clone();
+    SequencesToProductions::new(&mut eager).rewrite(sequence.clone());
+    let mut rewrite = SequencesToProductions::new(&mut cfg);
+    rewrite.set_max_rules(Some(1000));
+    assert_eq!(rewrite.try_rewrite(sequence), Ok(()));
+    drop(rewrite);
+    let rules = |cfg: &Cfg| {
+        cfg.rules().map(|rule| (rule.lhs(), rule.rhs().to_vec())).collect::<Vec<_>>()
+    };
+    assert_eq!(rules(&cfg).len(), rules(&eager).len());
+}
+
+#[test]
+fn test_try_rewrite_shared_helpers() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem) = cfg.sym();
+    let sequence = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 1,
+        end: Some(20),
+        separator: Null,
+        history: NullHistory,
+    };
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg).share_helpers(true);
+        rewrite.set_max_rules(Some(3));
+        assert!(rewrite.try_rewrite(sequence.clone()).is_err());
+        rewrite.set_max_rules(None);
+        assert_eq!(rewrite.try_rewrite(sequence), Ok(()));
+    }
+    let recognizer = Recognizer::new(&cfg);
+    assert!(!recognizer.recognize(list, &[]));
+    for n in 1..21 {
+        assert!(recognizer.recognize(list, &vec![elem; n]));
+    }
+    assert!(!recognizer.recognize(list, &[elem; 21]));
+}
+
+#[test]
+fn test_try_rewrite_linear_limits() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem, sep) = cfg.sym();
+    let sequence = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 1,
+        end: Some(1_000_000),
+        separator: Proper(sep),
+        history: NullHistory,
+    };
+    {
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        rewrite.set_expansion_strategy(ExpansionStrategy::Linear);
+        rewrite.set_max_rules(Some(10));
+        assert_eq!(rewrite.try_rewrite(sequence.clone()).map_err(|err| err.kind),
+                   Err(SequenceErrorKind::TooManyRules(10)));
+        // Stops before the eleventh rule.
+        assert_eq!(rewrite.rewrite_stats().rules_emitted, 10);
+        let symbols_emitted = rewrite.rewrite_stats().symbols_emitted;
+        rewrite.set_max_rules(None);
+        rewrite.set_max_symbols(Some(20));
+        assert_eq!(rewrite.try_rewrite(sequence.clone()).map_err(|err| err.kind),
+                   Err(SequenceErrorKind::TooManySymbols(20)));
+        // Rules of 1, 3, 5 and 7 symbols fit, 9 more don't.
+        assert_eq!(rewrite.rewrite_stats().symbols_emitted - symbols_emitted, 16);
+        assert_eq!(rewrite.try_rewrite(sequence.inclusive(1, Some(4))), Ok(()));
+    }
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(list, &[elem, sep, elem, sep, elem, sep, elem]));
+    assert!(!recognizer.recognize(list, &[elem, sep, elem, sep, elem, sep, elem, sep, elem]));
+}
+
+#[test]
+fn test_shared_helpers() {
+    let shared_helpers = |end| {
+        let mut cfg: Cfg = Cfg::new();
+        let (list, elem) = cfg.sym();
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        rewrite.rewrite(Sequence {
+            lhs: list,
+            rhs: elem,
+            start: 1,
+            end: Some(end),
+            separator: Null,
+            history: NullHistory,
+        });
+        rewrite.shared_helpers().len()
+    };
+    assert!(shared_helpers(8) > shared_helpers(7));
+}
+
+#[test]
+fn test_closure_destination() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem, sep) = cfg.sym();
+    let mut sequences = vec![];
+    {
+        let mut push = |seq: Sequence<NullHistory, NumericSymbol>| sequences.push(seq);
+        // The closure is coerced to a trait object, which is a destination.
+        let destination: &mut FnMut(Sequence<NullHistory, NumericSymbol>) = &mut push;
+        SequenceRuleBuilder::new(destination).sequence(list)
+                                             .intersperse(sep)
+                                             .rhs(elem, 1..);
+    }
+    assert_eq!(sequences.len(), 1);
+    assert_eq!((sequences[0].lhs(), sequences[0].rhs()), (list, elem));
+    assert_eq!(sequences[0].separator(), Proper(sep));
+}
+
+#[test]
+fn test_fn_destination() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, elem) = cfg.sym();
+    let mut sequences = vec![];
+    {
+        let destination = FnDestination::new(|seq: Sequence<NullHistory, NumericSymbol>| {
+            sequences.push(seq)
+        });
+        SequenceRuleBuilder::new(destination).sequence(list).rhs(elem, 2..3);
+    }
+    assert_eq!(sequences.len(), 1);
+    assert_eq!((sequences[0].start, sequences[0].end), (2, Some(2)));
+}
+
+#[test]
+fn test_rule_vec() {
+    let mut rules = RuleVec::new();
+    let (list, elem, sep): (NumericSymbol, _, _) = rules.sym();
+    SequencesToProductions::new(&mut rules).rewrite(Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 1,
+        end: None,
+        separator: Proper(sep),
+        history: NullHistory,
+    });
+
+    let rules = rules.into_rules();
+    let rules = rules.iter().map(|rule| (rule.lhs(), rule.rhs.clone())).collect::<Vec<_>>();
+    assert_eq!(rules, vec![(list, vec![elem]), (list, vec![list, sep, elem])]);
+}
+
+#[test]
+fn test_expand() {
+    let mut symbols = ConsecutiveSymbols::new();
+    let (list, elem, sep): (NumericSymbol, _, _) = symbols.sym();
+    let seq = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 2,
+        end: Some(4),
+        separator: Proper(sep),
+        history: NullHistory,
+    };
+    let rules = SequencesToProductions::expand(seq.clone(), &mut symbols);
+
+    let mut expected = RuleVec::new();
+    let _: (NumericSymbol, NumericSymbol, NumericSymbol) = expected.sym();
+    SequencesToProductions::new(&mut expected).rewrite(seq);
+    assert_eq!(symbols.num_syms(), expected.num_syms());
+    let pairs = |rules: &[Rule<NullHistory, NumericSymbol>]| {
+        rules.iter().map(|rule| (rule.lhs(), rule.rhs.clone())).collect::<Vec<_>>()
+    };
+    assert_eq!(pairs(&rules), pairs(expected.rules()));
+}
+
+#[test]
+fn test_rule_vec_retain_rules() {
+    let mut rules: RuleVec<u32> = RuleVec::new();
+    let (a, b, c): (NumericSymbol, _, _) = rules.sym();
+    rules.add_rule(a, &[b, c], 1);
+    rules.add_rule(a, &[], 2);
+    rules.add_rule(b, &[c], 3);
+    rules.add_rule(c, &[], 4);
+    rules.retain_rules(|rule| rule.lhs != a || !rule.rhs.is_empty());
+    (&mut rules).retain_rules(|rule| *rule.history != 3);
+
+    let rules = rules.rules().iter().map(|rule| {
+        (rule.lhs(), rule.rhs.clone(), rule.history)
+    }).collect::<Vec<_>>();
+    assert_eq!(rules, vec![(a, vec![b, c], 1), (c, vec![], 4)]);
+
+    let mut cfg: Cfg<NullHistory, NullHistory> = Cfg::new();
+    let start = cfg.start_sym();
+    let (x, y) = cfg.sym();
+    cfg.rule(start).rhs([x])
+                   .rhs([x, y]);
+    cfg.retain_rules(|rule| rule.rhs.len() == 2);
+    let rules = cfg.rules().map(|rule| rule.rhs.to_vec()).collect::<Vec<_>>();
+    assert_eq!(rules, vec![vec![x, y]]);
+}
+
+#[test]
+fn test_rule_vec_remap() {
+    let mut rules = RuleVec::new();
+    let (_unused, list, elem, sep): (NumericSymbol, NumericSymbol, _, _) = rules.sym();
+    SequencesToProductions::new(&mut rules).rewrite(Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 0,
+        end: None,
+        separator: Proper(sep),
+        history: NullHistory,
+    });
+    let old_rules = rules.rules().iter().map(|rule| {
+        (rule.lhs(), rule.rhs.clone())
+    }).collect::<Vec<_>>();
+
+    let map = rules.remap();
+    assert_eq!(map.to_new(_unused), None);
+    assert_eq!(map.len(), 4);
+    assert_eq!(rules.num_syms(), 6);
+    let old = |sym| map.to_old(sym).unwrap();
+    let new_rules = rules.rules().iter().map(|rule| {
+        (old(rule.lhs()), rule.rhs.iter().map(|&sym| old(sym)).collect::<Vec<_>>())
+    }).collect::<Vec<_>>();
+    assert_eq!(new_rules, old_rules);
+    let mut ids = map.iter().map(|(_, new)| new.usize()).collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(ids, vec![2, 3, 4, 5]);
+}
+
+fn rewrite_with_nullable_separator(separator: fn(NumericSymbol) -> Separator<NumericSymbol>,
+                                   treatment: NullableSeparator)
+                                   -> Result<(Cfg, NumericSymbol), SequenceError<NumericSymbol>> {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (item, sep, comma) = cfg.sym();
+    // sep ::= epsilon | comma
+    cfg.rule(sep).rhs([]).rhs([comma]);
+    try!({
+        let mut rewrite = SequencesToProductions::new(&mut cfg);
+        rewrite.set_nullable_symbols(&[sep]);
+        rewrite.set_nullable_separator(treatment);
+        rewrite.try_rewrite(Sequence {
+            lhs: start,
+            rhs: item,
+            start: 1,
+            end: None,
+            separator: separator(sep),
+            history: NullHistory,
+        })
+    });
+    Ok((cfg, start))
+}
+
+#[test]
+fn test_nullable_separator() {
+    let (cfg, start) = rewrite_with_nullable_separator(Liberal, NullableSeparator::Allow).unwrap();
+    assert!(cfg.ambiguity_rate(start, 4) > 0.0);
+
+    let disambiguate = NullableSeparator::Disambiguate;
+    let (cfg, start) = rewrite_with_nullable_separator(Liberal, disambiguate).unwrap();
+    assert_eq!(cfg.ambiguity_rate(start, 4), 0.0);
+    let (trailing, _) = rewrite_with_nullable_separator(Trailing, disambiguate).unwrap();
+    support::assert_eq_rules(trailing.rules(), cfg.rules());
+    assert_eq!(trailing.ambiguity_rate(start, 4), 0.0);
+
+    let reject = NullableSeparator::Reject;
+    for &separator in &[Proper as fn(_) -> _, Trailing, Liberal] {
+        let error = rewrite_with_nullable_separator(separator, reject).err().unwrap();
+        assert_eq!(error.kind, SequenceErrorKind::NullableSeparator);
+    }
+    assert!(rewrite_with_nullable_separator(|_| Separator::Null, reject).is_ok());
+}
+
+#[test]
+fn test_sequences_equivalent() {
+    let (list, other, elem, sep) = Cfg::<NullHistory>::new().sym();
+    let seq = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 0,
+        end: None,
+        separator: Separator::Null,
+        history: NullHistory,
+    };
+    let equivalent = |a: Sequence<NullHistory, NumericSymbol>, b: Sequence<_, _>| {
+        sequences_equivalent(&a, &b)
+    };
+
+    assert!(equivalent(seq.clone().inclusive(0, Some(0)), seq.clone().at_most(0)));
+    assert!(equivalent(seq.clone().inclusive(1, Some(1)), seq.clone().repeat(1)));
+    assert!(equivalent(seq.clone().at_most(0).with_separator(Liberal(sep)),
+                       seq.clone().at_most(0)));
+    assert!(equivalent(seq.clone().repeat(1).with_separator(Proper(sep)), seq.clone().repeat(1)));
+    let mut renamed = seq.clone().at_least(2);
+    renamed.lhs = other;
+    assert!(equivalent(renamed, seq.clone().at_least(2)));
+
+    assert!(!equivalent(seq.clone().inclusive(1, Some(3)), seq.clone().inclusive(1, Some(4))));
+    assert!(!equivalent(seq.clone().at_least(1), seq.clone().at_least(0)));
+    assert!(!equivalent(seq.clone().repeat(1).with_separator(Trailing(sep)),
+                        seq.clone().repeat(1)));
+    assert!(!equivalent(seq.clone().repeat(2).with_separator(Proper(sep)), seq.clone().repeat(2)));
+}
+
+#[test]
+fn test_unbounded_from_n() {
+    for &min in &[2, 3, 5] {
+        for &kind in &[Proper as fn(_) -> _, Trailing, Liberal, |_| Separator::Null] {
+            let mut cfg: Cfg = Cfg::new();
+            let start = cfg.start_sym();
+            let (item, sep) = cfg.sym();
+            let separator = kind(sep);
+            cfg.sequence(start).separator(separator).rhs(item, min..);
+            cfg.rewrite_sequences();
+
+            // Sentences of up to 9 symbols, for all counts of items.
+            let mut expected = BTreeSet::new();
+            for count in min as usize .. 10 {
+                let sentence = |trailing| {
+                    let mut sentence = vec![];
+                    for i in 0 .. count {
+                        sentence.push(item);
+                        if trailing || i + 1 != count {
+                            sentence.push(sep);
+                        }
+                    }
+                    sentence
+                };
+                match separator {
+                    Proper(_) => { expected.insert(sentence(false)); }
+                    Trailing(_) => { expected.insert(sentence(true)); }
+                    Liberal(_) => {
+                        expected.insert(sentence(false));
+                        expected.insert(sentence(true));
+                    }
+                    Separator::Null => { expected.insert(vec![item; count]); }
+                }
+            }
+            let expected = expected.into_iter().filter(|sentence| {
+                sentence.len() <= 9
+            }).collect::<BTreeSet<_>>();
+
+            let recognizer = Recognizer::new(&cfg);
+            let mut accepted = BTreeSet::new();
+            for len in 0 .. 10 {
+                for bits in 0 .. 1u32 << len {
+                    let sentence = (0 .. len).map(|i| {
+                        if bits & (1 << i) == 0 { item } else { sep }
+                    }).collect::<Vec<_>>();
+                    if recognizer.recognize(start, &sentence) {
+                        accepted.insert(sentence);
+                    }
+                }
+            }
+            assert_eq!(accepted, expected);
+            assert_eq!(cfg.ambiguity_rate(start, 9), 0.0);
+        }
+    }
+}
+
+#[test]
+fn test_rewrite_as_start() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, item, sep) = cfg.sym();
+    let start = cfg.rewrite_as_start(Sequence {
+        lhs: list,
+        rhs: item,
+        start: 0,
+        end: None,
+        separator: Proper(sep),
+        history: NullHistory,
+    });
+
+    assert_eq!(cfg.start_symbols(), vec![start]);
+    assert_eq!(cfg.start_sym(), start);
+    assert!(cfg.rules().all(|rule| !rule.rhs().contains(&start)));
+    let start_rules = cfg.rules().filter(|rule| rule.lhs() == start).map(|rule| {
+        rule.rhs().to_vec()
+    }).collect::<Vec<_>>();
+    assert_eq!(start_rules, vec![vec![list]]);
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(start, &[]));
+    assert!(recognizer.recognize(start, &[item, sep, item]));
+}
+
+#[test]
+fn test_max_bounds() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (item, sep, block) = cfg.sym();
+    cfg.sequence(start).separator(Proper(sep))
+                       .inclusive(0, Some(u32::MAX))
+                       .rhs_with_history(item, NullHistory);
+    cfg.sequence(block).inclusive(u32::MAX, Some(u32::MAX)).rhs_with_history(item, NullHistory);
+    cfg.rewrite_sequences();
+
+    assert!(cfg.rules().count() < 1000);
+    let recognizer = Recognizer::new(&cfg);
+    assert!(recognizer.recognize(start, &[]));
+    assert!(recognizer.recognize(start, &[item]));
+    assert!(recognizer.recognize(start, &[item, sep, item, sep, item, sep, item, sep, item]));
+    assert!(!recognizer.recognize(start, &[item, sep]));
+    assert!(!recognizer.recognize(block, &[item, item, item]));
+
+    let mut rules = RuleVec::new();
+    let (list, item, sep): (NumericSymbol, _, _) = rules.sym();
+    for &(start, end) in &[(0, Some(u32::MAX)), (u32::MAX, Some(u32::MAX)), (u32::MAX, None)] {
+        let mut rewrite = SequencesToProductions::new(&mut rules);
+        rewrite.set_max_rules(Some(1000));
+        let result = rewrite.try_rewrite(Sequence {
+            lhs: list,
+            rhs: item,
+            start: start,
+            end: end,
+            separator: Trailing(sep),
+            history: NullHistory,
+        });
+        assert_eq!(result, Ok(()));
+    }
+}
+
+#[test]
+fn test_pretty() {
+    let mut rules = RuleVec::new();
+    let (list, elem, sep): (NumericSymbol, _, _) = rules.sym();
+    let names = |sym: NumericSymbol| {
+        let named = [(list, "list"), (elem, "elem"), (sep, "sep")];
+        named.iter().find(|&&(named_sym, _)| named_sym == sym).map_or_else(|| {
+            format!("s{}", sym.usize())
+        }, |&(_, name)| name.to_string())
+    };
+    let seq = Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 0,
+        end: None,
+        separator: Proper(sep),
+        history: NullHistory,
+    };
+    assert_eq!(seq.pretty(&names), "list ::= elem{0,} % sep");
+    assert_eq!(seq.clone().inclusive(2, Some(5)).with_separator(Trailing(sep)).pretty(&names),
+               "list ::= elem{2,5} %% sep");
+    assert_eq!(seq.clone().with_separator(Liberal(sep)).pretty(&names), "list ::= elem{0,} %? sep");
+    assert_eq!(seq.clone().with_separator(Separator::Null).pretty(&names), "list ::= elem{0,}");
+
+    SequencesToProductions::new(&mut rules).rewrite(seq);
+    let printed = rules.rules().iter().map(|rule| rule.pretty(&names)).collect::<Vec<_>>();
+    assert_eq!(printed, vec!["list ::= ε", "list ::= s5", "s5 ::= elem", "s5 ::= s5 sep elem"]);
+}
+
+#[test]
+fn test_relax_bounds() {
+    let mut cfg: Cfg = Cfg::new();
+    let (list, star, elem, sep) = cfg.sym();
+    cfg.sequence(list).intersperse(sep).rhs(elem, 2..101);
+    cfg.sequence(star).rhs(elem, 0..);
+    let mut bounded = cfg.clone();
+    bounded.rewrite_sequences();
+    let bounded_rules = bounded.rules().count();
+
+    assert_eq!(cfg.relax_bounds(), vec![(list, 2, Some(100))]);
+    cfg.rewrite_sequences();
+    assert!(cfg.rules().count() < bounded_rules);
+
+    let mut equivalent: Cfg = Cfg::new();
+    let (list, star, elem, sep) = equivalent.sym();
+    equivalent.sequence(list).intersperse(sep).rhs(elem, 2..);
+    equivalent.sequence(star).rhs(elem, 0..);
+    equivalent.rewrite_sequences();
+
+    // Order is significant.
+    support::assert_eq_rules(equivalent.rules(), cfg.rules());
+}
+
+#[test]
+fn test_rule_vec_write_dot() {
+    let mut rules = RuleVec::new();
+    let (list, elem, sep): (NumericSymbol, _, _) = rules.sym();
+    SequencesToProductions::new(&mut rules).rewrite(Sequence {
+        lhs: list,
+        rhs: elem,
+        start: 0,
+        end: None,
+        separator: Proper(sep),
+        history: NullHistory,
+    });
+
+    let label = |sym| {
+        if sym == list {
+            Some("list".to_string())
+        } else if sym == elem {
+            Some("\"elem\"".to_string())
+        } else if sym == sep {
+            Some("sep".to_string())
+        } else {
+            None
+        }
+    };
+    let mut dot = String::new();
+    rules.write_dot(label, &mut dot).unwrap();
+    assert_eq!(dot, "digraph {\n    s2 [label=\"list\"];\n    s3 [label=\"\\\"elem\\\"\"];\n    \
+                     s4 [label=\"sep\"];\n    s5 [style=dashed];\n    \
+                     s2 -> s5;\n    s5 -> s3;\n    s5 -> s4;\n    s5 -> s5;\n}\n");
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "serde")]
+
+extern crate cfg;
+extern crate serde_json;
+
+use cfg::*;
+use cfg::history::NullHistory;
+use cfg::sequence::Sequence;
+use cfg::sequence::Separator::*;
+use cfg::symbol::NumericSymbol;
+
+#[test]
+fn test_rules_round_trip() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (list, elem, sep) = cfg.sym();
+    cfg.rule(start).rhs([list]);
+    cfg.sequence(list).separator(Liberal(sep)).rhs(elem, 0..4);
+    cfg.rewrite_sequences();
+
+    let rules = cfg.rules().cloned().collect::<Vec<_>>();
+    let json = serde_json::to_string(&rules).unwrap();
+    let loaded: Vec<Rule<NullHistory, NumericSymbol>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.len(), rules.len());
+    for (rule, loaded) in rules.iter().zip(loaded.iter()) {
+        assert_eq!((rule.lhs(), rule.rhs()), (loaded.lhs(), loaded.rhs()));
+    }
+    let refs = cfg.rules().map(|rule| {
+        RuleRef { lhs: rule.lhs(), rhs: rule.rhs(), history: rule.history() }
+    }).collect::<Vec<_>>();
+    assert_eq!(serde_json::to_string(&refs).unwrap(), json);
+}
+
+#[test]
+fn test_sequence_round_trip() {
+    let seq: Sequence<NullHistory, NumericSymbol> = Sequence {
+        lhs: NumericSymbol::from(2),
+        rhs: NumericSymbol::from(3),
+        start: 1,
+        end: Some(5),
+        separator: Trailing(NumericSymbol::from(4)),
+        history: NullHistory,
+    };
+    let json = serde_json::to_string(&seq).unwrap();
+    let loaded: Sequence<NullHistory, NumericSymbol> = serde_json::from_str(&json).unwrap();
+    assert_eq!((loaded.lhs, loaded.rhs, loaded.start, loaded.end, loaded.separator),
+               (seq.lhs, seq.rhs, seq.start, seq.end, seq.separator));
+    assert!(serde_json::from_str::<NumericSymbol>("0").is_err());
+}
@@ -0,0 +1,184 @@
+extern crate cfg;
+
+use cfg::*;
+use cfg::history::NullHistory;
+use cfg::symbol::{ClassifiedSymbols, GrammarSymbol, NumericSymbol, TerminalSymbolSet};
+use cfg::rule_container::RuleContainer;
+use cfg::usefulness::{GrammarDefect, HealthReport, Usefulness};
+
+#[test]
+fn test_set_terminals() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, b) = cfg.sym();
+    cfg.rule(start).rhs([a])
+                   .rhs([b]);
+    assert!(!Usefulness::with_terminal_set(&mut cfg).has_useless_rules());
+
+    cfg.sym_source_mut().set_terminals(&[a]);
+    assert!(cfg.is_terminal(a));
+    assert!(!cfg.is_terminal(b));
+    {
+        let usefulness = Usefulness::with_terminal_set(&mut cfg);
+        assert!(usefulness.has_useless_rules());
+        assert!(usefulness.productivity(a));
+        assert!(!usefulness.productivity(b));
+    }
+    Usefulness::with_terminal_set(&mut cfg).remove_useless_rules();
+    assert_eq!(cfg.rules().map(|rule| rule.rhs().to_vec()).collect::<Vec<_>>(), vec![vec![a]]);
+
+    // Without the classification, symbols without rules are terminals.
+    cfg.rule(start).rhs([b]);
+    assert!(!Usefulness::new(&mut cfg).has_useless_rules());
+}
+
+#[test]
+fn test_nullable_productivity() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (x, useless) = cfg.sym();
+    cfg.rule(start).rhs([])
+                   .rhs([start, x])
+                   .rhs([useless]);
+    cfg.rule(useless).rhs([useless, x]);
+
+    let usefulness = Usefulness::new(&mut cfg);
+    assert!(usefulness.productivity(start));
+    assert!(!usefulness.productivity(useless));
+}
+
+#[test]
+fn test_multiple_start_symbols() {
+    let mut cfg: Cfg = Cfg::new();
+    let start = cfg.start_sym();
+    let (expr, stmt, a, b) = cfg.sym();
+    cfg.rule(start).rhs([a]);
+    cfg.rule(expr).rhs([a, b]);
+    cfg.rule(stmt).rhs([expr, b]);
+    assert!(Usefulness::new(&mut cfg).has_useless_rules());
+
+    cfg.add_start(stmt);
+    assert_eq!(cfg.start_symbols(), vec![start, stmt]);
+    assert!(!Usefulness::new(&mut cfg).has_useless_rules());
+
+    let augmented = cfg.augment_start();
+    assert_eq!(cfg.start_symbols(), vec![augmented]);
+    assert!(!Usefulness::new(&mut cfg).has_useless_rules());
+    assert_eq!(cfg.rules().filter(|rule| rule.lhs() == augmented)
+                          .map(|rule| rule.rhs().to_vec())
+                          .collect::<Vec<_>>(),
+               vec![vec![start], vec![stmt]]);
+}
+
+#[test]
+fn test_undefined_symbols() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, b) = cfg.sym();
+    let (typo, expr) = (cfg.next_sym(false), cfg.next_sym(false));
+    cfg.rule(start).rhs([a, expr])
+                   .rhs([typo, b]);
+    cfg.rule(expr).rhs([b]);
+
+    let undefined = cfg.undefined_symbols();
+    assert_eq!(undefined.iter().collect::<Vec<_>>(), vec![typo]);
+
+    cfg.sym_source_mut().set_terminals(&[a]);
+    let undefined = cfg.undefined_symbols();
+    assert_eq!(undefined.iter().collect::<Vec<_>>(), vec![b, typo]);
+}
+
+#[test]
+fn test_validate() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let (a, undefined, term) = cfg.sym();
+    cfg.sym_source_mut().set_terminals(&[term]);
+    cfg.rule(start).rhs([a]);
+    cfg.rule(a).rhs([undefined]);
+    assert_eq!(Usefulness::with_terminal_set(&mut cfg).validate(),
+               Err(GrammarDefect::UnproductiveStart(start)));
+
+    cfg.rule(a).rhs([term]);
+    assert_eq!(Usefulness::with_terminal_set(&mut cfg).validate(), Ok(()));
+}
+
+#[test]
+fn test_set_terminals_grows() {
+    let mut sym_source = ClassifiedSymbols::new();
+    let (a, b): (NumericSymbol, NumericSymbol) = (5.into(), 7.into());
+    sym_source.set_terminals(&[a, b]);
+    assert_eq!(sym_source.num_syms(), 8);
+    assert!(sym_source.is_terminal(a) && sym_source.is_terminal(b));
+    assert!(!sym_source.is_terminal(6.into()));
+    let c = sym_source.next_sym(false);
+    assert!(c != a && c != b && !sym_source.is_terminal(c));
+}
+
+#[test]
+fn test_register_tokens() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let tokens = cfg.sym_source_mut().register_tokens(vec![("num", 2), ("plus", 3), ("minus", 4)]);
+    let (num, plus, minus) = (tokens["num"], tokens["plus"], tokens["minus"]);
+    assert_eq!(num, ClassifiedSymbols::token(2));
+    assert_eq!((num.usize(), plus.usize(), minus.usize()), (2, 3, 4));
+    let op = cfg.next_sym(false);
+    assert_eq!(cfg.sym_source().name_of(plus), Some("plus"));
+    assert_eq!(cfg.sym_source().name_of(op), None);
+    cfg.rule(start).rhs([num])
+                   .rhs([start, op, num]);
+    cfg.rule(op).rhs([plus])
+                .rhs([minus]);
+
+    assert!(cfg.is_terminal(num) && cfg.is_terminal(plus) && cfg.is_terminal(minus));
+    assert!(!cfg.is_terminal(op));
+    assert!(op != num && op != plus && op != minus);
+    assert!(!Usefulness::with_terminal_set(&mut cfg).has_useless_rules());
+}
+
+#[test]
+#[should_panic]
+fn test_register_start_token() {
+    ClassifiedSymbols::new().register_tokens(vec![("num", 1)]);
+}
+
+#[test]
+fn test_health_report() {
+    let mut cfg: Cfg<NullHistory, NullHistory, ClassifiedSymbols> =
+        Cfg::with_sym_source(ClassifiedSymbols::new());
+    let start = cfg.start_sym();
+    let x = cfg.sym();
+    let (nullable, cyclic, other, unproductive, typo, unreachable) = (
+        cfg.next_sym(false), cfg.next_sym(false), cfg.next_sym(false),
+        cfg.next_sym(false), cfg.next_sym(false), cfg.next_sym(false),
+    );
+    cfg.rule(start).rhs([nullable])
+                   .rhs([cyclic])
+                   .rhs([unproductive])
+                   .rhs([typo]);
+    cfg.rule(nullable).rhs([nullable, x])
+                      .rhs([]);
+    cfg.rule(cyclic).rhs([other])
+                    .rhs([x]);
+    cfg.rule(other).rhs([cyclic]);
+    cfg.rule(unproductive).rhs([unproductive, x]);
+    cfg.rule(unreachable).rhs([x]);
+
+    let report = cfg.health_report(start);
+    assert_eq!(report, HealthReport {
+        unreachable: vec![unreachable],
+        unproductive: vec![unproductive],
+        undefined: vec![typo],
+        cyclic: vec![cyclic, other],
+        nullable_left_recursive: vec![nullable],
+    });
+    assert!(!report.is_healthy());
+
+    cfg.retain(|lhs, rhs, _| lhs == start && rhs == [cyclic] || lhs == cyclic && rhs == [x]);
+    assert!(cfg.health_report(start).is_healthy());
+}